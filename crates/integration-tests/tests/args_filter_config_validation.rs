@@ -61,7 +61,7 @@ args_filter $bad_include_mode {
     include equals "^x$";
 }
 "#,
-        expected_stderr: "expects literal, \"~\", or \"~*\"",
+        expected_stderr: "expects literal, \"=*\", \"~\", or \"~*\"",
     },
     Case {
         name: "exclude_mode_must_be_regex_operator_when_three_args",
@@ -71,7 +71,37 @@ args_filter $bad_exclude_mode {
     exclude equals "^x$";
 }
 "#,
-        expected_stderr: "expects literal, \"~\", or \"~*\"",
+        expected_stderr: "expects literal, \"=*\", \"~\", or \"~*\"",
+    },
+    Case {
+        name: "prefix_pattern_must_not_be_empty",
+        conf: r"
+args_filter $bad_prefix {
+    initial all;
+    exclude prefix:;
+}
+",
+        expected_stderr: "\"prefix:\" requires a non-empty pattern",
+    },
+    Case {
+        name: "exclude_value_mode_must_be_regex_operator",
+        conf: r#"
+args_filter $bad_exclude_value {
+    initial all;
+    exclude_value equals "^x$";
+}
+"#,
+        expected_stderr: "\"exclude_value\" expects \"~\" or \"~*\"",
+    },
+    Case {
+        name: "value_regex_must_compile",
+        conf: r#"
+args_filter $bad_value_regex {
+    initial all;
+    exclude callback value~ "(";
+}
+"#,
+        expected_stderr: "failed to compile regex:",
     },
     Case {
         name: "exclude_regex_must_compile",
@@ -132,6 +162,629 @@ args_filter $bad_volatile_arity {
 ",
         expected_stderr: "invalid number of arguments in \"volatile\" directive",
     },
+    Case {
+        name: "duplicate_declaration_in_same_location_is_rejected",
+        conf: r"
+server {
+    listen 8080;
+    server_name dup.local;
+
+    location / {
+        args_filter $dup_location {
+            initial all;
+        }
+
+        args_filter $dup_location {
+            initial none;
+        }
+    }
+}
+",
+        expected_stderr: "duplicate args_filter declaration for $dup_location",
+    },
+    Case {
+        name: "extends_undeclared_filter_is_rejected",
+        conf: r"
+args_filter $extends_missing {
+    extends $not_declared;
+}
+",
+        expected_stderr: "\"extends\" references undeclared args_filter $not_declared",
+    },
+    Case {
+        name: "extends_self_reference_is_rejected",
+        conf: r"
+args_filter $extends_self {
+    extends $extends_self;
+}
+",
+        expected_stderr: "\"extends\" references undeclared args_filter $extends_self",
+    },
+    Case {
+        name: "extends_duplicate_is_rejected",
+        conf: r"
+args_filter $extends_base_a {
+    initial all;
+}
+
+args_filter $extends_base_b {
+    initial none;
+}
+
+args_filter $extends_twice {
+    extends $extends_base_a;
+    extends $extends_base_b;
+}
+",
+        expected_stderr: "\"extends\" directive is duplicate",
+    },
+    Case {
+        name: "extends_after_rules_is_rejected",
+        conf: r"
+args_filter $extends_late_base {
+    initial all;
+}
+
+args_filter $extends_late {
+    exclude token;
+    extends $extends_late_base;
+}
+",
+        expected_stderr: "\"extends\" must be the first directive in the block",
+    },
+    Case {
+        name: "extends_wrong_arity_rejected",
+        conf: r"
+args_filter $extends_arity {
+    extends;
+}
+",
+        expected_stderr: "invalid number of arguments in \"extends\" directive",
+    },
+    Case {
+        name: "include_variable_operand_must_be_valid",
+        conf: r"
+args_filter $bad_variable_operand {
+    initial none;
+    include $;
+}
+",
+        expected_stderr: "invalid variable name",
+    },
+    Case {
+        name: "if_condition_requires_value",
+        conf: r"
+args_filter $empty_condition {
+    initial all;
+    exclude debug if=;
+}
+",
+        expected_stderr: "\"if=\" requires a value",
+    },
+    Case {
+        name: "if_condition_with_extra_operand_is_rejected",
+        conf: r#"
+args_filter $condition_extra_operand {
+    initial all;
+    exclude ~ "^x" extra if=$arg_x;
+}
+"#,
+        expected_stderr: "invalid number of arguments in \"exclude\" directive",
+    },
+    Case {
+        name: "rules_file_wrong_arity_rejected",
+        conf: r"
+args_filter $bad_rules_file_arity {
+    initial all;
+    rules_file;
+}
+",
+        expected_stderr: "invalid number of arguments in \"rules_file\" directive",
+    },
+    Case {
+        name: "key_list_reference_must_be_declared",
+        conf: r"
+args_filter $unknown_key_list {
+    initial all;
+    exclude @missing;
+}
+",
+        expected_stderr: "unknown args_filter_keys list @missing",
+    },
+    Case {
+        name: "key_list_duplicate_declaration_is_rejected",
+        conf: r"
+args_filter_keys dup_list {
+    a;
+}
+
+args_filter_keys dup_list {
+    b;
+}
+",
+        expected_stderr: "duplicate args_filter_keys declaration for @dup_list",
+    },
+    Case {
+        name: "key_list_name_invalid_characters",
+        conf: r"
+args_filter_keys bad-list {
+    a;
+}
+",
+        expected_stderr: "args_filter_keys list name contains invalid characters",
+    },
+    Case {
+        name: "key_list_entry_mode_must_be_regex_operator",
+        conf: r#"
+args_filter_keys bad_mode {
+    equals "^x$";
+}
+"#,
+        expected_stderr: "entry expects literal, \"=*\", \"~\", or \"~*\"",
+    },
+    Case {
+        name: "key_list_entry_regex_must_compile",
+        conf: r#"
+args_filter_keys bad_regex {
+    ~ "(";
+}
+"#,
+        expected_stderr: "failed to compile regex:",
+    },
+    Case {
+        name: "key_list_is_http_context_only",
+        conf: r"
+server {
+    listen 8080;
+    server_name keys.local;
+
+    args_filter_keys in_server {
+        a;
+    }
+}
+",
+        expected_stderr: "\"args_filter_keys\" directive is not allowed here",
+    },
+    Case {
+        name: "zone_reference_must_be_declared",
+        conf: r"
+args_filter $unknown_zone {
+    initial all;
+    exclude @zone:missing;
+}
+",
+        expected_stderr: "unknown args_filter_zone missing",
+    },
+    Case {
+        name: "zone_duplicate_declaration_is_rejected",
+        conf: r"
+args_filter_zone dup_zone 1m;
+args_filter_zone dup_zone 1m;
+",
+        expected_stderr: "duplicate args_filter_zone declaration for dup_zone",
+    },
+    Case {
+        name: "zone_size_must_be_valid",
+        conf: r"
+args_filter_zone bad_size lots;
+",
+        expected_stderr: "invalid size \"lots\" in \"args_filter_zone\" directive",
+    },
+    Case {
+        name: "zone_size_too_small",
+        conf: r"
+args_filter_zone tiny 1k;
+",
+        expected_stderr: "args_filter_zone \"tiny\" is too small",
+    },
+    Case {
+        name: "strict_rejects_duplicate_literal",
+        conf: r"
+args_filter_strict on;
+
+args_filter $strict_duplicate {
+    initial all;
+    exclude token;
+    exclude token;
+}
+",
+        expected_stderr: "rule[1] exclude \"token\" repeats the literal of rule[0] exclude \"token\"",
+    },
+    Case {
+        name: "strict_rejects_rule_overridden_by_catch_all",
+        conf: r#"
+args_filter_strict on;
+
+args_filter $strict_catch_all {
+    initial none;
+    include a;
+    exclude ~ ".*";
+    include b;
+}
+"#,
+        expected_stderr: "rule[0] include \"a\" is always overridden by catch-all rule[1] exclude ~ \".*\"",
+    },
+    Case {
+        name: "strict_rejects_exclude_without_effect_under_initial_none",
+        conf: r"
+args_filter_strict on;
+
+args_filter $strict_noop_exclude {
+    initial none;
+    include a;
+    exclude b;
+}
+",
+        expected_stderr: "rule[1] exclude \"b\" has no effect under \"initial none\"",
+    },
+    Case {
+        name: "strict_rejects_always_empty_filter",
+        conf: r"
+args_filter_strict on;
+
+args_filter $strict_empty {
+    initial none;
+}
+",
+        expected_stderr: "args_filter $strict_empty: filter always produces an empty value",
+    },
+    Case {
+        name: "strict_must_precede_args_filter_blocks",
+        conf: r"
+args_filter $before_strict {
+    initial all;
+}
+
+args_filter_strict on;
+",
+        expected_stderr: "\"args_filter_strict\" must precede all \"args_filter\" blocks",
+    },
+    Case {
+        name: "match_directive_duplicate",
+        conf: r"
+args_filter $dup_match {
+    match first;
+    match last;
+}
+",
+        expected_stderr: "\"match\" directive is duplicate",
+    },
+    Case {
+        name: "match_value_must_be_first_or_last",
+        conf: r"
+args_filter $bad_match {
+    match any;
+}
+",
+        expected_stderr: "\"match\" must be \"first\" or \"last\"",
+    },
+    Case {
+        name: "extends_must_precede_match",
+        conf: r"
+args_filter $match_base {
+    initial all;
+}
+
+args_filter $match_derived {
+    match first;
+    extends $match_base;
+}
+",
+        expected_stderr: "\"extends\" must be the first directive in the block",
+    },
+    Case {
+        name: "strict_rejects_rule_after_catch_all_in_first_match_mode",
+        conf: r#"
+args_filter_strict on;
+
+args_filter $strict_first_match {
+    initial all;
+    match first;
+    include keep;
+    exclude ~ ".*";
+    include never;
+}
+"#,
+        expected_stderr: "rule[2] include \"never\" is never reached after catch-all rule[1] exclude ~ \".*\"",
+    },
+    Case {
+        name: "decode_keys_directive_duplicate",
+        conf: r"
+args_filter $dup_decode_keys {
+    decode_keys on;
+    decode_keys off;
+}
+",
+        expected_stderr: "\"decode_keys\" directive is duplicate",
+    },
+    Case {
+        name: "decode_keys_value_must_be_on_or_off",
+        conf: r"
+args_filter $bad_decode_keys {
+    decode_keys yes;
+}
+",
+        expected_stderr: "\"decode_keys\" must be \"on\" or \"off\"",
+    },
+    Case {
+        name: "decode_keys_invalid_policy_is_rejected",
+        conf: r"
+args_filter $bad_invalid_policy {
+    decode_keys on invalid=ignore;
+}
+",
+        expected_stderr: "\"decode_keys\" expects \"invalid=literal\", \"invalid=drop\", or \"invalid=keep\"",
+    },
+    Case {
+        name: "array_keys_directive_duplicate",
+        conf: r"
+args_filter $dup_array_keys {
+    array_keys on;
+    array_keys off;
+}
+",
+        expected_stderr: "\"array_keys\" directive is duplicate",
+    },
+    Case {
+        name: "array_keys_value_must_be_on_or_off",
+        conf: r"
+args_filter $bad_array_keys {
+    array_keys yes;
+}
+",
+        expected_stderr: "\"array_keys\" must be \"on\" or \"off\"",
+    },
+    Case {
+        name: "path_rule_with_array_keys_is_rejected",
+        conf: r"
+args_filter $array_path {
+    array_keys on;
+    exclude path:filter[*][password];
+}
+",
+        expected_stderr: "\"path:\" rules cannot be used with \"array_keys on\"",
+    },
+    Case {
+        name: "array_keys_after_inherited_path_rule_is_rejected",
+        conf: r"
+args_filter $path_base {
+    exclude path:*.secret;
+}
+
+args_filter $path_array {
+    extends $path_base;
+    array_keys on;
+}
+",
+        expected_stderr: "\"path:\" rules cannot be used with \"array_keys on\"",
+    },
+    Case {
+        name: "path_separator_value_must_be_known",
+        conf: r"
+args_filter $bad_path_separator {
+    path_separator slash;
+}
+",
+        expected_stderr: "\"path_separator\" must be \"brackets\", \"dot\", or \"both\"",
+    },
+    Case {
+        name: "path_separator_must_precede_rules",
+        conf: r"
+args_filter $late_path_separator {
+    exclude path:a.b;
+    path_separator dot;
+}
+",
+        expected_stderr: "\"path_separator\" must precede \"include\" and \"exclude\"",
+    },
+    Case {
+        name: "empty_values_value_must_be_known",
+        conf: r"
+args_filter $bad_empty_values {
+    empty_values as_empty;
+}
+",
+        expected_stderr: "\"empty_values\" must be \"keep\" or \"drop\"",
+    },
+    Case {
+        name: "valueless_keys_duplicate",
+        conf: r"
+args_filter $duplicate_valueless_keys {
+    valueless_keys drop;
+    valueless_keys keep;
+}
+",
+        expected_stderr: "\"valueless_keys\" directive is duplicate",
+    },
+    Case {
+        name: "regex_match_limit_must_be_positive",
+        conf: r"
+args_filter $zero_match_limit {
+    regex_match_limit 0;
+}
+",
+        expected_stderr: "\"regex_match_limit\" must be a positive integer",
+    },
+    Case {
+        name: "regex_depth_limit_must_be_a_number",
+        conf: r"
+args_filter $bad_depth_limit {
+    regex_depth_limit deep;
+}
+",
+        expected_stderr: "\"regex_depth_limit\" must be a positive integer",
+    },
+    Case {
+        name: "regex_match_limit_duplicate",
+        conf: r"
+args_filter $duplicate_match_limit {
+    regex_match_limit 1000;
+    regex_match_limit 2000;
+}
+",
+        expected_stderr: "\"regex_match_limit\" directive is duplicate",
+    },
+    Case {
+        name: "regex_limit_exceeded_value_must_be_known",
+        conf: r"
+args_filter $bad_limit_policy {
+    regex_limit_exceeded ignore;
+}
+",
+        expected_stderr: "\"regex_limit_exceeded\" must be \"keep\", \"drop\", or \"fail\"",
+    },
+    Case {
+        name: "strict_rejects_nested_quantifier",
+        conf: r#"
+args_filter_strict on;
+
+args_filter $strict_nested_quantifier {
+    initial all;
+    exclude ~ "^(a+)+$";
+}
+"#,
+        expected_stderr: "rule[0] exclude ~ \"^(a+)+$\" repeats a group with an unbounded quantifier and may backtrack catastrophically",
+    },
+    Case {
+        name: "validate_type_must_be_known",
+        conf: r"
+args_filter $bad_validate_type {
+    validate limit number;
+}
+",
+        expected_stderr: "\"validate\" type must be \"int\", \"uint\", \"uuid\", \"hex\", \"base64\", \"bool\", \"enum(...)\", or \"range(min,max)\"",
+    },
+    Case {
+        name: "validate_range_bounds_must_be_ordered",
+        conf: r"
+args_filter $bad_validate_range {
+    validate limit range(100,1);
+}
+",
+        expected_stderr: "\"validate\" range must be \"range(min,max)\" with integer min <= max",
+    },
+    Case {
+        name: "validate_enum_values_must_not_be_empty",
+        conf: r"
+args_filter $bad_validate_enum {
+    validate sort enum(asc,,desc);
+}
+",
+        expected_stderr: "\"validate\" enum values must not be empty",
+    },
+    Case {
+        name: "validate_default_must_be_valid",
+        conf: r"
+args_filter $bad_validate_default {
+    validate limit range(1,100) default=500;
+}
+",
+        expected_stderr: "\"validate\" default \"500\" is not a valid range(1,100)",
+    },
+    Case {
+        name: "validate_duplicate_key",
+        conf: r"
+args_filter $duplicate_validate {
+    validate limit int;
+    validate limit uint;
+}
+",
+        expected_stderr: "\"validate\" is duplicate for key \"limit\"",
+    },
+    Case {
+        name: "max_value_length_must_be_positive",
+        conf: r"
+args_filter $bad_max_value_length {
+    max_value_length q 0 truncate;
+}
+",
+        expected_stderr: "\"max_value_length\" must be a positive integer",
+    },
+    Case {
+        name: "max_value_length_duplicate_key",
+        conf: r"
+args_filter $duplicate_max_value_length {
+    max_value_length 512;
+    max_value_length q 64;
+    max_value_length q 128 truncate;
+}
+",
+        expected_stderr: "\"max_value_length\" is duplicate for key \"q\"",
+    },
+    Case {
+        name: "rename_requires_regex_operator",
+        conf: r"
+args_filter $bad_rename {
+    rename = q query;
+}
+",
+        expected_stderr: "\"rename\" expects \"~\" or \"~*\" before a regex",
+    },
+    Case {
+        name: "rename_replacement_references_missing_capture",
+        conf: r#"
+args_filter $bad_rename_capture {
+    rename ~ "^aws\.(.*)$" "$2";
+}
+"#,
+        expected_stderr: "\"rename\" replacement refers to missing capture \"$2\"",
+    },
+    Case {
+        name: "rename_replacement_rejects_variables",
+        conf: r#"
+args_filter $bad_rename_variable {
+    rename ~ "^aws\.(.*)$" "$arg_prefix$1";
+}
+"#,
+        expected_stderr: "\"rename\" replacement may only use \"$\" in \"$0\" to \"$9\"",
+    },
+    Case {
+        name: "rename_duplicate_key",
+        conf: r"
+args_filter $duplicate_rename {
+    rename q query;
+    rename q search;
+}
+",
+        expected_stderr: "\"rename\" is duplicate for key \"q\"",
+    },
+    Case {
+        name: "rename_literal_target_rejects_separators",
+        conf: r#"
+args_filter $bad_rename_target {
+    rename q "query&admin=1";
+}
+"#,
+        expected_stderr: "\"rename\" replacement must not contain spaces, \"&\", \"=\", or \"#\"",
+    },
+    Case {
+        name: "rename_regex_replacement_rejects_separators",
+        conf: r#"
+args_filter $bad_rename_template {
+    rename ~ "^aws\.(.*)$" "$1#x";
+}
+"#,
+        expected_stderr: "\"rename\" replacement must not contain spaces, \"&\", \"=\", or \"#\"",
+    },
+    Case {
+        name: "add_key_rejects_separators",
+        conf: r#"
+args_filter $bad_add_key {
+    add "a&b" 1;
+}
+"#,
+        expected_stderr: "\"add\" key must not contain spaces, \"&\", \"=\", or \"#\"",
+    },
+    Case {
+        name: "set_duplicate_key",
+        conf: r"
+args_filter $duplicate_set {
+    set tenant $host;
+    set tenant default;
+}
+",
+        expected_stderr: "\"set\" is duplicate for key \"tenant\"",
+    },
 ];
 
 const NGINX_CONF: &str = r#"
@@ -180,3 +833,97 @@ async fn test_args_filter_config_validation_errors_matrix() {
         assert_error_fragment(&output, case.name, case.expected_stderr);
     }
 }
+
+#[tokio::test]
+async fn test_rules_file_errors_report_rules_file_and_line() {
+    let nginx = helpers::setup_nginx(NGINX_CONF);
+    let rules_dir = tempfile::tempdir().expect("rules dir");
+
+    let cases = [
+        (
+            "rules_file_invalid_regex",
+            "exclude token\n\nexclude ~ \"(\"\n",
+            "failed to compile regex:",
+        ),
+        (
+            "rules_file_unknown_directive",
+            "exclude token\ninitial all\n",
+            "only \"include\", \"exclude\", and \"exclude_value\" are allowed in rules files",
+        ),
+        (
+            "rules_file_terminated_rule",
+            "exclude token\nexclude signature;\n",
+            "unexpected \";\", rules in a rules file are not terminated",
+        ),
+    ];
+
+    for (name, contents, expected) in cases {
+        let rules_path = rules_dir.path().join(format!("{name}.rules"));
+        std::fs::write(&rules_path, contents).expect("write rules file");
+        let conf = format!(
+            "
+args_filter ${name} {{
+    initial all;
+    rules_file {};
+}}
+",
+            rules_path.display()
+        );
+
+        let (stdout, stderr) = helpers::run_nginx_config_test(&nginx, name, &conf);
+        let output = format!("{stdout}\n{stderr}");
+        let line = contents.lines().count();
+        assert_nginx_t_failed(&output, name);
+        assert_error_fragment(&output, name, expected);
+        assert_error_fragment(
+            &output,
+            name,
+            &format!("{}:{line}", rules_path.display()),
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_rules_file_missing_file_is_rejected() {
+    let nginx = helpers::setup_nginx(NGINX_CONF);
+    let conf = r"
+args_filter $missing_rules_file {
+    initial all;
+    rules_file /nonexistent/ngx-args-filter/missing.rules;
+}
+";
+
+    let (stdout, stderr) = helpers::run_nginx_config_test(&nginx, "missing_rules_file", conf);
+    let output = format!("{stdout}\n{stderr}");
+    assert_nginx_t_failed(&output, "missing_rules_file");
+    assert_error_fragment(
+        &output,
+        "missing_rules_file",
+        "/nonexistent/ngx-args-filter/missing.rules",
+    );
+}
+
+#[tokio::test]
+async fn test_lint_findings_are_warnings_without_strict() {
+    let nginx = helpers::setup_nginx(NGINX_CONF);
+    let conf = r"
+args_filter $lint_warning {
+    initial all;
+    exclude token;
+    exclude token;
+}
+";
+
+    let (stdout, stderr) = helpers::run_nginx_config_test(&nginx, "lint_warning", conf);
+    let output = format!("{stdout}\n{stderr}");
+    assert!(
+        output.contains("test is successful"),
+        "expected nginx -t to succeed for `lint_warning`. output was:\n{output}"
+    );
+    assert_error_fragment(&output, "lint_warning", "[warn]");
+    assert_error_fragment(
+        &output,
+        "lint_warning",
+        "args_filter $lint_warning: rule[1] exclude \"token\" repeats the literal of rule[0]",
+    );
+}
@@ -236,3 +236,1489 @@ server {
     assert_eq!(response.status(), 200);
     assert_eq!(response.text().await.unwrap(), "keep=%2B&keep2=a%2Bb");
 }
+
+#[tokio::test]
+async fn test_args_filter_server_and_location_definitions_override_outer_scopes() {
+    let nginx_conf = r#"
+args_filter $shared_args {
+    initial none;
+    include a;
+}
+
+args_filter $scoped_args {
+    initial none;
+    include a;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    args_filter $scoped_args {
+        initial none;
+        include b;
+    }
+
+    location / {
+        default_type text/plain;
+        return 200 "$shared_args|$scoped_args";
+    }
+
+    location /loc {
+        args_filter $scoped_args {
+            initial none;
+            include c;
+        }
+
+        location /loc/nested {
+            default_type text/plain;
+            return 200 "$shared_args|$scoped_args";
+        }
+
+        default_type text/plain;
+        return 200 "$shared_args|$scoped_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(&nginx, "/", Some("a=1&b=2&c=3")).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1|b=2");
+
+    let response = helpers::send_request(&nginx, "/loc", Some("a=1&b=2&c=3")).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1|c=3");
+
+    let response = helpers::send_request(&nginx, "/loc/nested", Some("a=1&b=2&c=3")).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1|c=3");
+}
+
+#[tokio::test]
+async fn test_args_filter_defined_only_in_location_is_empty_elsewhere() {
+    let nginx_conf = r#"
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "[$local_args]";
+    }
+
+    location /local {
+        args_filter $local_args {
+            initial all;
+            exclude drop;
+        }
+
+        default_type text/plain;
+        return 200 "[$local_args]";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(&nginx, "/", Some("keep=1&drop=2")).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "[]");
+
+    let response = helpers::send_request(&nginx, "/local", Some("keep=1&drop=2")).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "[keep=1]");
+}
+
+#[tokio::test]
+async fn test_args_filter_extends_copies_base_rules_and_appends_local_rules() {
+    let nginx_conf = r#"
+args_filter $base_args {
+    initial all;
+    exclude token;
+    exclude ~ "^utm_";
+}
+
+args_filter $derived_args {
+    extends $base_args;
+    include utm_source;
+    exclude debug;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$base_args|$derived_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(
+        &nginx,
+        "/",
+        Some("a=1&token=x&utm_source=s&utm_medium=m&debug=1"),
+    )
+    .await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&debug=1|a=1&utm_source=s"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_extends_local_directives_replace_inherited_ones() {
+    let nginx_conf = r#"
+args_filter $base_args {
+    initial all;
+    validate limit uint default=20;
+    max_value_length q 4 truncate;
+    rename q query;
+    set page 1;
+}
+
+args_filter $derived_args {
+    extends $base_args;
+    validate limit range(1,5) default=5;
+    max_value_length q 8 truncate;
+    rename q search;
+    set page 2;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$base_args|$derived_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response =
+        helpers::send_request(&nginx, "/", Some("q=abcdefghij&limit=50&page=9")).await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "query=abcd&limit=50&page=1|search=abcdefgh&limit=5&page=2"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_extends_in_location_can_refine_outer_definition() {
+    let nginx_conf = r#"
+args_filter $upstream_args {
+    initial all;
+    exclude token;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        args_filter $upstream_args {
+            extends $upstream_args;
+            initial none;
+            include a;
+        }
+
+        default_type text/plain;
+        return 200 "$upstream_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(&nginx, "/", Some("a=1&b=2&token=x")).await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1");
+}
+
+#[tokio::test]
+async fn test_args_filter_extends_in_nested_location_uses_enclosing_location() {
+    let nginx_conf = r#"
+args_filter $upstream_args {
+    initial all;
+    exclude token;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location /api {
+        args_filter $upstream_args {
+            extends $upstream_args;
+            exclude session;
+        }
+
+        args_filter $api_args {
+            initial none;
+            include a;
+        }
+
+        location /api/v2 {
+            args_filter $upstream_args {
+                extends $upstream_args;
+                exclude debug;
+            }
+
+            args_filter $v2_args {
+                extends $api_args;
+                include b;
+            }
+
+            default_type text/plain;
+            return 200 "$upstream_args|$v2_args";
+        }
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(
+        &nginx,
+        "/api/v2",
+        Some("a=1&b=2&token=x&session=y&debug=z"),
+    )
+    .await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1&b=2|a=1&b=2");
+}
+
+#[tokio::test]
+async fn test_args_filter_keys_list_expands_in_include_and_exclude() {
+    let nginx_conf = r#"
+args_filter_keys secrets {
+    token;
+    signature;
+    ~* "^x-amz-";
+}
+
+args_filter $public_args {
+    initial all;
+    exclude @secrets;
+}
+
+args_filter $secret_args {
+    initial none;
+    include @secrets;
+    exclude signature;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$public_args|$secret_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(
+        &nginx,
+        "/",
+        Some("a=1&token=t&X-Amz-Date=d&signature=s&b=2"),
+    )
+    .await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&b=2|token=t&X-Amz-Date=d"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_rules_file_loads_rules_from_glob_in_order() {
+    let rules_dir = tempfile::tempdir().expect("rules dir");
+    std::fs::write(
+        rules_dir.path().join("10-deny.rules"),
+        "# owned by security\nexclude token  # session tokens\n\nexclude ~* \"^x-amz-\"\n",
+    )
+    .expect("write deny rules");
+    std::fs::write(
+        rules_dir.path().join("20-allow.rules"),
+        "include X-Amz-Keep\n",
+    )
+    .expect("write allow rules");
+
+    let nginx_conf = r#"
+args_filter $filtered_args {
+    initial all;
+    rules_file __RULES_DIR__/*.rules;
+    exclude debug;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$filtered_args";
+    }
+}
+"#
+    .replace("__RULES_DIR__", rules_dir.path().to_string_lossy().as_ref());
+
+    let nginx = helpers::setup_nginx(&nginx_conf);
+
+    let response = helpers::send_request(
+        &nginx,
+        "/",
+        Some("a=1&token=t&X-Amz-Date=d&X-Amz-Keep=k&debug=1"),
+    )
+    .await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1&X-Amz-Keep=k");
+}
+
+#[tokio::test]
+async fn test_args_filter_variable_operands_are_evaluated_per_request() {
+    let nginx_conf = r#"
+args_filter $filtered_args {
+    initial none;
+    include a;
+    include $arg_keep;
+    include $http_x_keep_param;
+    exclude $http_x_strip_param;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$filtered_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(&nginx, "/", Some("a=1&b=2&c=3&keep=b")).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1&b=2");
+
+    let response = helpers::send_request(&nginx, "/", Some("a=1&b=2&c=3")).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1");
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("http://127.0.0.1:{}/?a=1&b=2&c=3&keep=b", nginx.port))
+        .header("X-Keep-Param", "c")
+        .header("X-Strip-Param", "a")
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "b=2&c=3");
+}
+
+#[tokio::test]
+async fn test_args_filter_if_condition_enables_rules_per_request() {
+    let nginx_conf = r#"
+map $http_x_external $is_external {
+    default 0;
+    yes     1;
+}
+
+args_filter_keys internal_only {
+    trace;
+}
+
+args_filter $filtered_args {
+    initial all;
+    exclude debug if=$is_external;
+    exclude ~ "^internal_" if=$is_external;
+    exclude @internal_only if=$is_external;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$filtered_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let url = format!(
+        "http://127.0.0.1:{}/?a=1&debug=1&internal_id=2&trace=3",
+        nginx.port
+    );
+    let client = reqwest::Client::new();
+
+    let response = client.get(&url).send().await.expect("Failed to send request");
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&debug=1&internal_id=2&trace=3"
+    );
+
+    let response = client
+        .get(&url)
+        .header("X-External", "yes")
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1");
+}
+
+#[tokio::test]
+async fn test_args_filter_zone_updates_rules_at_runtime() {
+    let nginx_conf = r#"
+args_filter_zone secrets 1m;
+
+args_filter $filtered_args {
+    initial all;
+    exclude @zone:secrets;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location = /args_filter_api {
+        args_filter_api;
+    }
+
+    location / {
+        default_type text/plain;
+        return 200 "$filtered_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let api = format!("http://127.0.0.1:{}/args_filter_api", nginx.port);
+    let client = reqwest::Client::new();
+
+    let response = helpers::send_request(&nginx, "/", Some("a=1&token=2&sig=3")).await;
+    assert_eq!(response.text().await.unwrap(), "a=1&token=2&sig=3");
+
+    for key in ["token", "sig"] {
+        let response = client
+            .post(format!("{api}?zone=secrets&key={key}"))
+            .send()
+            .await
+            .expect("Failed to send request");
+        assert_eq!(response.status(), 204);
+    }
+
+    let response = client
+        .get(format!("{api}?zone=secrets"))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "sig\ntoken\n");
+
+    let response = helpers::send_request(&nginx, "/", Some("a=1&token=2&sig=3")).await;
+    assert_eq!(response.text().await.unwrap(), "a=1");
+
+    let response = client
+        .delete(format!("{api}?zone=secrets&key=token"))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 204);
+
+    let response = client
+        .delete(format!("{api}?zone=secrets&key=token"))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 404);
+
+    let response = helpers::send_request(&nginx, "/", Some("a=1&token=2&sig=3")).await;
+    assert_eq!(response.text().await.unwrap(), "a=1&token=2");
+
+    let response = client
+        .get(format!("{api}?zone=unknown"))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .post(format!("{api}?zone=secrets"))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_args_filter_dump_reports_loaded_filters() {
+    let nginx_conf = r#"
+args_filter $base_args {
+    initial all;
+    exclude token;
+    exclude ~* "^x-amz-" if=$arg_strip;
+    validate limit range(1,100) default=20;
+    max_value_length q 8 truncate;
+    rename ~ "^aws\.(.*)$" "$1";
+    add client_ip $remote_addr;
+}
+
+args_filter $derived_args {
+    extends $base_args;
+    include "$arg_keep";
+    validate sort enum(asc,desc);
+    volatile;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location = /args_filter_dump {
+        args_filter_dump;
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let response = helpers::send_request(&nginx, "/args_filter_dump", None).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok()),
+        Some("application/json")
+    );
+
+    let dump: serde_json::Value = response.json().await.expect("valid JSON dump");
+    let base = serde_json::json!({
+        "name": "base_args", "scope": "http",
+        "initial": "all",
+        "match": "last",
+        "decode_keys": false,
+        "invalid_escapes": "literal",
+        "array_keys": false,
+        "path_separator": "both",
+        "empty_values": "keep",
+        "valueless_keys": "keep",
+        "regex_match_limit": 0,
+        "regex_depth_limit": 0,
+        "regex_limit_exceeded": "drop",
+        "volatile": false,
+        "extends": null,
+        "rules": [
+            {
+                "action": "exclude",
+                "kind": "literal",
+                "pattern": "token",
+                "case_insensitive": false,
+                "condition": null,
+                "value": null
+            },
+            {
+                "action": "exclude",
+                "kind": "regex",
+                "pattern": "^x-amz-",
+                "case_insensitive": true,
+                "condition": "$arg_strip",
+                "value": null
+            }
+        ],
+        "validations": [{ "key": "limit", "type": "range(1,100)", "default": "20" }],
+        "max_value_lengths": [{ "key": "q", "length": 8, "action": "truncate" }],
+        "renames": [{ "kind": "regex", "from": "^aws\\.(.*)$", "case_insensitive": false, "to": "$1" }],
+        "injected_args": [{ "action": "add", "key": "client_ip", "value": "$remote_addr" }]
+    });
+    let mut derived = base.clone();
+    derived["name"] = "derived_args".into();
+    derived["volatile"] = true.into();
+    derived["extends"] = "base_args".into();
+    derived["rules"]
+        .as_array_mut()
+        .expect("rules array")
+        .push(serde_json::json!({
+            "action": "include",
+            "kind": "variable",
+            "pattern": "$arg_keep",
+            "case_insensitive": false,
+            "condition": null,
+            "value": null
+        }));
+    derived["validations"]
+        .as_array_mut()
+        .expect("validations array")
+        .push(serde_json::json!({ "key": "sort", "type": "enum(asc,desc)", "default": null }));
+    assert_eq!(dump, serde_json::json!({ "args_filters": [base, derived] }));
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/args_filter_dump", nginx.port))
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(response.status(), 405);
+}
+
+#[tokio::test]
+async fn test_args_filter_dump_reports_server_and_location_filters() {
+    let nginx_conf = r"
+args_filter $shared_args {
+    initial all;
+}
+
+server {
+    listen 8080 default_server;
+    server_name api.example.com www.example.com;
+
+    args_filter $shared_args {
+        initial none;
+    }
+
+    args_filter $server_args {
+        initial none;
+    }
+
+    location /search {
+        args_filter $shared_args {
+            initial all;
+            exclude token;
+        }
+
+        location /search/deep {
+            args_filter $deep_args {
+                initial all;
+            }
+        }
+    }
+
+    location = /args_filter_dump {
+        args_filter_dump;
+    }
+}
+";
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let response = helpers::send_request(&nginx, "/args_filter_dump", None).await;
+    assert_eq!(response.status(), 200);
+
+    let dump: serde_json::Value = response.json().await.expect("valid JSON dump");
+    let filters = dump["args_filters"].as_array().expect("args_filters array");
+    let scoped: Vec<(&str, &str, &str)> = filters
+        .iter()
+        .map(|f| {
+            (
+                f["scope"].as_str().unwrap(),
+                f["name"].as_str().unwrap(),
+                f["initial"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        scoped,
+        [
+            ("http", "shared_args", "all"),
+            ("server api.example.com", "server_args", "none"),
+            ("server api.example.com", "shared_args", "none"),
+            ("location /search", "shared_args", "all"),
+            ("location /search/deep", "deep_args", "all"),
+        ]
+    );
+    assert_eq!(filters[3]["rules"][0]["pattern"], "token");
+}
+
+#[tokio::test]
+async fn test_args_filter_match_first_stops_at_first_matching_rule() {
+    let nginx_conf = r#"
+args_filter $first_match_args {
+    initial none;
+    match first;
+    exclude secret_token;
+    include ~ "^secret_";
+    include a;
+}
+
+args_filter $last_match_args {
+    initial none;
+    exclude secret_token;
+    include ~ "^secret_";
+    include a;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location /first {
+        default_type text/plain;
+        return 200 "$first_match_args";
+    }
+
+    location /last {
+        default_type text/plain;
+        return 200 "$last_match_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let query = Some("a=1&secret_token=2&secret_id=3&b=4");
+
+    let response = helpers::send_request(&nginx, "/first", query).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1&secret_id=3");
+
+    let response = helpers::send_request(&nginx, "/last", query).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&secret_token=2&secret_id=3"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_decode_keys_matches_decoded_keys() {
+    let nginx_conf = r#"
+args_filter $raw_args {
+    initial all;
+    exclude access_token;
+}
+
+args_filter $decoded_args {
+    initial all;
+    decode_keys on;
+    exclude access_token;
+    exclude "x y";
+}
+
+args_filter $drop_invalid_args {
+    initial all;
+    decode_keys on invalid=drop;
+    exclude access_token;
+}
+
+args_filter $keep_invalid_args {
+    initial none;
+    decode_keys on invalid=keep;
+    include a;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location /raw {
+        default_type text/plain;
+        return 200 "$raw_args";
+    }
+
+    location /decoded {
+        default_type text/plain;
+        return 200 "$decoded_args";
+    }
+
+    location /drop_invalid {
+        default_type text/plain;
+        return 200 "$drop_invalid_args";
+    }
+
+    location /keep_invalid {
+        default_type text/plain;
+        return 200 "$keep_invalid_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let query = Some("a=1&ac%63ess_token=2&x+y=3&b%zz=4&%61=5");
+
+    let response = helpers::send_request(&nginx, "/raw", query).await;
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&ac%63ess_token=2&x+y=3&b%zz=4&%61=5"
+    );
+
+    let response = helpers::send_request(&nginx, "/decoded", query).await;
+    assert_eq!(response.text().await.unwrap(), "a=1&b%zz=4&%61=5");
+
+    let response = helpers::send_request(&nginx, "/drop_invalid", query).await;
+    assert_eq!(response.text().await.unwrap(), "a=1&x+y=3&%61=5");
+
+    let response = helpers::send_request(&nginx, "/keep_invalid", query).await;
+    assert_eq!(response.text().await.unwrap(), "a=1&b%zz=4&%61=5");
+}
+
+#[tokio::test]
+async fn test_args_filter_invalid_drop_applies_without_rules() {
+    let nginx_conf = r#"
+args_filter $drop_invalid_args {
+    initial all;
+    decode_keys on invalid=drop;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$drop_invalid_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(&nginx, "/", Some("a=1&b%zz=2&%61=3")).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1&%61=3");
+}
+
+#[tokio::test]
+async fn test_args_filter_caseless_literal_ignores_ascii_case() {
+    let nginx_conf = r#"
+args_filter_keys secrets {
+    =* signature;
+}
+
+args_filter $filtered_args {
+    initial all;
+    exclude =* token;
+    exclude @secrets;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$filtered_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(
+        &nginx,
+        "/",
+        Some("a=1&Token=t&TOKEN=u&token=v&tokens=w&Signature=s&b=2"),
+    )
+    .await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "a=1&tokens=w&b=2");
+}
+
+#[tokio::test]
+async fn test_args_filter_prefix_suffix_and_glob_matchers() {
+    let nginx_conf = r#"
+args_filter_keys tracking {
+    prefix:utm_;
+}
+
+args_filter $filtered_args {
+    initial all;
+    exclude @tracking;
+    exclude suffix:_token;
+    exclude glob:ads.*.id;
+}
+
+args_filter $aws_args {
+    initial none;
+    include prefix:aws.;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$filtered_args|$aws_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(
+        &nginx,
+        "/",
+        Some("a=1&utm_source=x&access_token=t&_token_x=2&ads.top.id=3&ads.id=4&aws.region=r&awsx=5"),
+    )
+    .await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&_token_x=2&ads.id=4&aws.region=r&awsx=5|aws.region=r"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_value_rules_drop_segments_by_value() {
+    let nginx_conf = r#"
+args_filter $filtered_args {
+    initial all;
+    exclude_value ~* "^javascript:";
+    exclude callback value~ "[^A-Za-z0-9_.]";
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$filtered_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let query = Some("a=1&next=JavaScript%3Aalert(1)&callback=cb.done&callback=x%3By&b=javascript");
+
+    let response = helpers::send_request(&nginx, "/", query).await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&callback=cb.done&b=javascript"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_array_keys_match_base_name() {
+    let nginx_conf = r#"
+args_filter $raw_args {
+    initial all;
+    exclude ids;
+}
+
+args_filter $array_args {
+    initial all;
+    array_keys on;
+    exclude ids;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location /raw {
+        default_type text/plain;
+        return 200 "$raw_args";
+    }
+
+    location /array {
+        default_type text/plain;
+        return 200 "$array_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let query = Some("a=1&ids[]=1&ids[0]=2&ids%5B%5D=3&ids%5B0%5D=4&ids=5&idsx[]=6");
+
+    let response = helpers::send_request(&nginx, "/raw", query).await;
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&ids[]=1&ids[0]=2&ids%5B%5D=3&ids%5B0%5D=4&idsx[]=6"
+    );
+
+    let response = helpers::send_request(&nginx, "/array", query).await;
+    assert_eq!(response.text().await.unwrap(), "a=1&idsx[]=6");
+}
+
+#[tokio::test]
+async fn test_args_filter_path_rules_match_nested_names() {
+    let nginx_conf = r#"
+args_filter $nested_args {
+    initial all;
+    exclude path:filter[*][password];
+    exclude path:*.secret;
+}
+
+args_filter $bracket_args {
+    initial all;
+    path_separator brackets;
+    exclude path:*[secret];
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location /nested {
+        default_type text/plain;
+        return 200 "$nested_args";
+    }
+
+    location /brackets {
+        default_type text/plain;
+        return 200 "$bracket_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let query = Some("filter[user][password]=1&filter.admin.password=2&filter[password]=3&api.secret=4&a.b[secret]=5&secret=6");
+
+    let response = helpers::send_request(&nginx, "/nested", query).await;
+    assert_eq!(
+        response.text().await.unwrap(),
+        "filter[password]=3&a.b[secret]=5&secret=6"
+    );
+
+    let response = helpers::send_request(&nginx, "/brackets", query).await;
+    assert_eq!(
+        response.text().await.unwrap(),
+        "filter[user][password]=1&filter.admin.password=2&filter[password]=3&api.secret=4&secret=6"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_literal_runs_keep_rule_order() {
+    let nginx_conf = r#"
+args_filter $last_args {
+    initial none;
+    include k0; include k1; include k2; include k3; include k4;
+    include k5; include k6; include k7; include k8; include k9;
+    exclude k3;
+    exclude k4; exclude k5; exclude k6; exclude k7; exclude k8;
+    exclude k9; exclude k10; exclude k11; exclude k12;
+    include k10;
+}
+
+args_filter $first_args {
+    extends $last_args;
+    match first;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$last_args|$first_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let query: String = (0..13)
+        .map(|i| format!("k{i}={i}"))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let response = helpers::send_request(&nginx, "/", Some(&query)).await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "k0=0&k1=1&k2=2&k10=10|k0=0&k1=1&k2=2&k3=3&k4=4&k5=5&k6=6&k7=7&k8=8&k9=9"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_regex_runs_keep_rule_order() {
+    let nginx_conf = r#"
+args_filter $last_args {
+    initial none;
+    include ~ ^utm_;
+    include ~* ^REF;
+    include ~ "^(x)\1$";
+    include k1;
+    exclude ~ "(?<n>_id)$";
+    exclude ~ "(?<n>_tmp)$";
+    exclude ~* ^UTM_TERM$;
+    include ~ ^utm_term_id$;
+}
+
+args_filter $first_args {
+    extends $last_args;
+    match first;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$last_args|$first_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let query = "utm_source=1&ref=2&xx=3&k1=4&utm_id=5&a_tmp=6&utm_term=7&other=8&utm_term_id=9";
+
+    let response = helpers::send_request(&nginx, "/", Some(query)).await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "utm_source=1&ref=2&xx=3&k1=4&utm_term_id=9|utm_source=1&ref=2&xx=3&k1=4&utm_id=5&utm_term=7&utm_term_id=9"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_regex_runs_keep_conditional_groups_separate() {
+    // Combined as `(?:^(z)y$)|(?:^(a)?(?(1)b|c)$)`, the condition would test
+    // the group of the first pattern and stop matching `ab`.
+    let nginx_conf = r#"
+args_filter $conditional_args {
+    initial all;
+    exclude ~ "^(z)y$";
+    exclude ~ "^(a)?(?(1)b|c)$";
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$conditional_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+    let response = helpers::send_request(&nginx, "/", Some("ab=1&c=2&zy=3&b=4&keep=5")).await;
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), "b=4&keep=5");
+}
+
+#[tokio::test]
+async fn test_args_filter_regex_limit_exceeded_policies() {
+    let nginx_conf = r#"
+args_filter $keep_args {
+    initial all;
+    regex_match_limit 1000;
+    regex_limit_exceeded keep;
+    exclude ~ "^(a+)+$";
+}
+
+args_filter $drop_args {
+    extends $keep_args;
+    regex_limit_exceeded drop;
+}
+
+args_filter $fail_args {
+    extends $keep_args;
+    regex_limit_exceeded fail;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$keep_args|$drop_args|$fail_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(&nginx, "/", Some("aaaa=1&x=2")).await;
+    assert_eq!(response.text().await.unwrap(), "x=2|x=2|x=2");
+
+    let key = format!("{}b", "a".repeat(30));
+    let query = format!("{key}=1&x=2");
+    let response = helpers::send_request(&nginx, "/", Some(&query)).await;
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.text().await.unwrap(), format!("{query}|x=2|"));
+}
+
+#[tokio::test]
+async fn test_args_filter_validate_drops_or_replaces_invalid_values() {
+    let nginx_conf = r#"
+args_filter $validated_args {
+    initial all;
+    decode_keys on;
+    array_keys on;
+    exclude debug;
+    validate limit range(1,100) default=20;
+    validate id uuid;
+    validate sort enum(asc,desc);
+    validate page uint;
+    validate ids int;
+    validate debug bool;
+    validate token base64;
+    validate tag enum(x,a&b) default=a&b;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$validated_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let cases = [
+        ("limit=abc&id=42&sort=asc&q=x", "limit=20&sort=asc&q=x"),
+        (
+            "limit=50&id=123e4567-e89b-12d3-a456-426614174000&sort=up",
+            "limit=50&id=123e4567-e89b-12d3-a456-426614174000",
+        ),
+        ("limit&page=-1&page=%32", "limit=20&page=%32"),
+        ("ids[]=1&ids[]=x&ids%5B%5D=-3", "ids[]=1&ids%5B%5D=-3"),
+        ("li%6Dit=0&debug=1", "li%6Dit=20"),
+        (
+            "token=aGVsbG8%3D&token=a+b/c&token=a%20b",
+            "token=aGVsbG8%3D&token=a+b/c",
+        ),
+        ("tag=y&tag=a%26b", "tag=a%26b&tag=a%26b"),
+    ];
+
+    for (query, expected) in cases {
+        let response = helpers::send_request(&nginx, "/", Some(query)).await;
+        assert_eq!(response.text().await.unwrap(), expected, "query={query}");
+    }
+}
+
+#[tokio::test]
+async fn test_args_filter_empty_values_and_valueless_keys() {
+    let nginx_conf = r#"
+args_filter $empty_dropped_args {
+    initial all;
+    empty_values drop;
+    valueless_keys drop;
+}
+
+args_filter $empty_normalized_args {
+    extends $empty_dropped_args;
+    valueless_keys as_empty;
+    exclude b;
+}
+
+args_filter $empty_validated_args {
+    initial all;
+    valueless_keys as_empty;
+    validate flag bool default=1;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$empty_dropped_args|$empty_normalized_args|$empty_validated_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let response = helpers::send_request(&nginx, "/", Some("flag&e=&a=1&b&c=%20")).await;
+    assert_eq!(
+        response.text().await.unwrap(),
+        "a=1&c=%20|flag=&a=1&c=%20|flag=1&e=&a=1&b=&c=%20"
+    );
+}
+
+#[tokio::test]
+async fn test_args_filter_max_value_length_drops_or_truncates() {
+    let nginx_conf = r#"
+args_filter $length_args {
+    initial all;
+    decode_keys on;
+    max_value_length 6;
+    max_value_length q 5 truncate;
+    max_value_length n 4 truncate;
+    validate n int;
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$length_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let cases = [
+        ("a=123456&b=1234567&q=abcdefgh", "a=123456&q=abcde"),
+        ("q=ab%41%42cd&%71=abcd%2", "q=ab%41&%71=abcd%"),
+        ("q=abcd%41&long=%41%42", "q=abcd&long=%41%42"),
+        ("n=12345&n=1x345&n=12", "n=1234&n=12"),
+    ];
+
+    for (query, expected) in cases {
+        let response = helpers::send_request(&nginx, "/", Some(query)).await;
+        assert_eq!(response.text().await.unwrap(), expected, "query={query}");
+    }
+}
+
+#[tokio::test]
+async fn test_args_filter_rename_rewrites_kept_keys() {
+    let nginx_conf = r#"
+args_filter $renamed_args {
+    initial all;
+    decode_keys on;
+    array_keys on;
+    exclude secret;
+    validate limit int default=20;
+    rename q query;
+    rename secret leaked;
+    rename limit lim;
+    rename ~ "^aws\.(.*)$" "$1";
+    rename ~* "^x_(.*)$" "x_$1";
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$renamed_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let cases = [
+        ("q=x&aws.region=eu&page=2", "query=x&region=eu&page=2"),
+        ("q[0]=a&%71%5B%5D=b&flag", "query[0]=a&query%5B%5D=b&flag"),
+        ("aws.a%26b=1&aws.=2", "a%26b=1&aws.=2"),
+        ("secret=1&X_Id=2&limit=abc", "x_Id=2&lim=20"),
+    ];
+
+    for (query, expected) in cases {
+        let response = helpers::send_request(&nginx, "/", Some(query)).await;
+        assert_eq!(response.text().await.unwrap(), expected, "query={query}");
+    }
+}
+
+#[tokio::test]
+async fn test_args_filter_rename_escapes_raw_captures() {
+    use std::io::{Read, Write};
+
+    let nginx_conf = r#"
+args_filter $renamed_args {
+    initial all;
+    rename ~ "^aws\.(.*)$" "$1";
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$renamed_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    // A client library would encode the non-ASCII key; nginx accepts it raw.
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", nginx.port)).unwrap();
+    stream
+        .write_all(b"GET /?aws.r\xc3\xa9=2&aws.c%26d+e=3 HTTP/1.0\r\n\r\n")
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+
+    let body = response
+        .split(|b| *b == b'\n')
+        .next_back()
+        .expect("response body");
+    assert_eq!(body, b"r%C3%A9=2&c%26d+e=3");
+}
+
+#[tokio::test]
+async fn test_args_filter_set_and_add_inject_args() {
+    let nginx_conf = r#"
+args_filter $injected_args {
+    initial none;
+    include q;
+    include p;
+    include page;
+    rename p page;
+    set page 1;
+    set tenant "t-$arg_t";
+    add client_ip $remote_addr;
+    add tag "a b&c";
+}
+
+server {
+    listen 8080 default_server;
+    server_name _;
+
+    location / {
+        default_type text/plain;
+        return 200 "$injected_args";
+    }
+}
+"#;
+
+    let nginx = helpers::setup_nginx(nginx_conf);
+
+    let cases = [
+        ("", "page=1&tenant=t-&client_ip=127.0.0.1&tag=a%20b%26c"),
+        (
+            "q=x&page=5&page=6&t=acme",
+            "q=x&page=1&tenant=t-acme&client_ip=127.0.0.1&tag=a%20b%26c",
+        ),
+        (
+            "p=9&q=y&t=a=b",
+            "q=y&page=1&tenant=t-a%3Db&client_ip=127.0.0.1&tag=a%20b%26c",
+        ),
+    ];
+
+    for (query, expected) in cases {
+        let response = helpers::send_request(&nginx, "/", Some(query)).await;
+        assert_eq!(response.text().await.unwrap(), expected, "query={query}");
+    }
+}
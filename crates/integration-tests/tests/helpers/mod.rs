@@ -19,6 +19,7 @@ pub struct NginxTestInstance {
     nginx_bin: PathBuf,
     main_conf: PathBuf,
     pub port: u16,
+    base_main_conf: String,
     base_http_conf: String,
 }
 
@@ -176,6 +177,7 @@ fn render_main_conf(
     module_path: &Path,
     root: &Path,
     port: u16,
+    main_conf: &str,
     http_conf: &str,
     error_log_path: &Path,
 ) -> String {
@@ -186,6 +188,7 @@ fn render_main_conf(
 worker_processes 1;
 error_log {} info;
 pid {};
+{}
 events {{ worker_connections 1024; }}
 http {{
   access_log off;
@@ -200,6 +203,7 @@ http {{
         module_path.display(),
         error_log_path.display(),
         pid.display(),
+        main_conf,
         tmp.display(),
         tmp.display(),
         tmp.display(),
@@ -220,6 +224,11 @@ fn wait_ready(port: u16) {
 }
 
 pub fn setup_nginx(nginx_conf: &str) -> NginxTestInstance {
+    setup_nginx_with_main("", nginx_conf)
+}
+
+/// Like [`setup_nginx`], with extra directives for the main context.
+pub fn setup_nginx_with_main(main_conf_extra: &str, nginx_conf: &str) -> NginxTestInstance {
     let artifacts = artifacts();
     let root = tempfile::Builder::new()
         .prefix("ngxaf-it-")
@@ -241,6 +250,7 @@ pub fn setup_nginx(nginx_conf: &str) -> NginxTestInstance {
         &artifacts.module_path,
         root.path(),
         port,
+        main_conf_extra,
         nginx_conf,
         &error_log,
     );
@@ -286,6 +296,7 @@ pub fn setup_nginx(nginx_conf: &str) -> NginxTestInstance {
         nginx_bin: artifacts.nginx_bin.clone(),
         main_conf,
         port,
+        base_main_conf: main_conf_extra.to_string(),
         base_http_conf: nginx_conf.to_string(),
     }
 }
@@ -315,6 +326,7 @@ pub fn run_nginx_config_test(
         &artifacts.module_path,
         nginx.root.path(),
         nginx.port,
+        &nginx.base_main_conf,
         &merged,
         &test_err,
     );
@@ -1,3 +1,6 @@
+mod helpers;
+
+use std::fmt::Write;
 use std::time::{Duration, Instant};
 
 fn filter_args_by<F>(args: &[u8], mut keep_key: F) -> Vec<u8>
@@ -57,3 +60,107 @@ fn perf_filter_args_harness() {
     let (p50, p95) = p50_p95(&mut samples);
     println!("perf_filter_args_harness: iterations={iterations} p50={p50:?} p95={p95:?}");
 }
+
+/// Allowlisted keys; every request sends a mix of them and unknown keys.
+const LITERALS: usize = 2_000;
+
+/// Literal runs shorter than this are scanned rule by rule (`LITERAL_RUN_MIN`).
+const LITERAL_RUN_MIN: usize = 8;
+
+/// The same `include` allowlist plus one `exclude` of a key that is never
+/// sent per `LITERAL_RUN_MIN - 1` includes.
+///
+/// With `indexed` the includes form one run and the excludes another, so both
+/// are hash lookups. Otherwise every exclude follows seven includes and ends
+/// their run, so no run is long enough to be indexed and each key is compared
+/// with every rule. The two filters keep the same keys.
+fn allowlist_conf(indexed: bool) -> String {
+    let include = |i: usize| format!("    include param_{i:04};\n");
+    let exclude = |i: usize| format!("    exclude never_sent_{i:04};\n");
+    let groups = LITERALS.div_ceil(LITERAL_RUN_MIN - 1);
+
+    let mut rules = String::new();
+    if indexed {
+        (0..LITERALS).for_each(|i| rules.push_str(&include(i)));
+        (0..groups).for_each(|i| rules.push_str(&exclude(i)));
+    } else {
+        for group in 0..groups {
+            let start = group * (LITERAL_RUN_MIN - 1);
+            (start..LITERALS.min(start + LITERAL_RUN_MIN - 1))
+                .for_each(|i| rules.push_str(&include(i)));
+            rules.push_str(&exclude(group));
+        }
+    }
+
+    format!(
+        r#"
+args_filter $filtered_args {{
+    initial none;
+{rules}}}
+
+server {{
+    listen 8080 default_server;
+    server_name _;
+
+    location / {{
+        default_type text/plain;
+        return 200 "$filtered_args";
+    }}
+}}
+"#
+    )
+}
+
+async fn measure_http(
+    nginx: &helpers::NginxTestInstance,
+    query: &str,
+    iterations: usize,
+) -> (String, Duration, Duration) {
+    let client = reqwest::Client::new();
+    let url = format!("http://127.0.0.1:{}/?{query}", nginx.port);
+    let body = client.get(&url).send().await.unwrap().text().await.unwrap();
+
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let response = client.get(&url).send().await.unwrap();
+        let _ = response.bytes().await.unwrap();
+        samples.push(start.elapsed());
+    }
+
+    let (p50, p95) = p50_p95(&mut samples);
+    (body, p50, p95)
+}
+
+#[tokio::test]
+#[ignore = "starts nginx twice; run with --ignored --nocapture"]
+async fn perf_literal_rules_scanned_vs_indexed_runs() {
+    // Half the keys are allowlisted late in the list, half match nothing:
+    // both are the worst case for a scan.
+    let mut query = String::new();
+    for i in 0..200 {
+        let key = if i % 2 == 0 {
+            format!("param_{:04}", LITERALS - 1 - i)
+        } else {
+            format!("unknown_{i}")
+        };
+        let sep = if query.is_empty() { "" } else { "&" };
+        let _ = write!(query, "{sep}{key}={i}");
+    }
+    let iterations = 300usize;
+
+    let scanned = helpers::setup_nginx(&allowlist_conf(false));
+    let (scanned_body, scanned_p50, scanned_p95) = measure_http(&scanned, &query, iterations).await;
+    drop(scanned);
+
+    let indexed = helpers::setup_nginx(&allowlist_conf(true));
+    let (indexed_body, indexed_p50, indexed_p95) = measure_http(&indexed, &query, iterations).await;
+
+    assert_eq!(scanned_body, indexed_body);
+    assert_eq!(scanned_body.matches('&').count() + 1, 100);
+    println!(
+        "perf_literal_rule_runs: rules={LITERALS} keys=200 iterations={iterations} \
+         scanned p50={scanned_p50:?} p95={scanned_p95:?} \
+         indexed p50={indexed_p50:?} p95={indexed_p95:?}"
+    );
+}
@@ -0,0 +1,122 @@
+mod helpers;
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Regex rules in the filter.
+const RULES: usize = 240;
+
+/// Keys per request; each one is matched against every rule.
+const KEYS: usize = 120;
+
+fn p50(durations: &mut [Duration]) -> Duration {
+    durations.sort_unstable();
+    durations[durations.len() / 2]
+}
+
+/// Unanchored regex rules with alternating actions, so no two adjacent rules
+/// share a run and every key costs one regex execution per rule, each scanning
+/// the whole key. `/baseline` returns the unfiltered arguments and measures
+/// everything but the filter.
+fn regex_heavy_conf() -> String {
+    let mut rules = String::new();
+    for i in 0..RULES {
+        let action = if i % 2 == 0 { "include" } else { "exclude" };
+        let _ = writeln!(
+            rules,
+            "    {action} ~* \"[a-z]+_{i}_(id|ref|[0-9]{{2,4}})$\";"
+        );
+    }
+
+    format!(
+        r#"
+args_filter $filtered_args {{
+    initial all;
+{rules}}}
+
+server {{
+    listen 8080 default_server;
+    server_name _;
+
+    location / {{
+        default_type text/plain;
+        return 200 "$filtered_args";
+    }}
+
+    location /baseline {{
+        default_type text/plain;
+        return 200 "$args";
+    }}
+}}
+"#
+    )
+}
+
+/// Median latency of `path?query` over `iterations` requests on one connection.
+async fn measure(
+    client: &reqwest::Client,
+    nginx: &helpers::NginxTestInstance,
+    path: &str,
+    query: &str,
+    iterations: usize,
+) -> (String, Duration) {
+    let url = format!("http://127.0.0.1:{}{path}?{query}", nginx.port);
+    let body = client.get(&url).send().await.unwrap().text().await.unwrap();
+
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let response = client.get(&url).send().await.unwrap();
+        let _ = response.bytes().await.unwrap();
+        samples.push(start.elapsed());
+    }
+
+    (body, p50(&mut samples))
+}
+
+/// Filter latency per regex execution: the filtered request minus the baseline.
+async fn per_match(
+    nginx: &helpers::NginxTestInstance,
+    query: &str,
+    iterations: usize,
+) -> (String, Duration, Duration) {
+    let client = reqwest::Client::new();
+    let (body, filtered) = measure(&client, nginx, "/", query, iterations).await;
+    let (_, baseline) = measure(&client, nginx, "/baseline", query, iterations).await;
+
+    let executions = u32::try_from(RULES * KEYS).unwrap();
+    (
+        body,
+        filtered.saturating_sub(baseline) / executions,
+        baseline,
+    )
+}
+
+#[tokio::test]
+#[ignore = "starts nginx twice; run with --ignored --nocapture"]
+async fn perf_regex_rules_with_and_without_pcre_jit() {
+    let conf = regex_heavy_conf();
+    // Long keys that match no rule make every execution scan the whole key.
+    let query = (0..KEYS)
+        .map(|i| format!("k{i}_{}_value={i}", "abcdefghij".repeat(3)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let iterations = 200usize;
+
+    let interpreted = helpers::setup_nginx_with_main("pcre_jit off;", &conf);
+    let (interpreted_body, interpreted_match, interpreted_baseline) =
+        per_match(&interpreted, &query, iterations).await;
+    drop(interpreted);
+
+    let jit = helpers::setup_nginx_with_main("pcre_jit on;", &conf);
+    let (jit_body, jit_match, jit_baseline) = per_match(&jit, &query, iterations).await;
+
+    assert_eq!(interpreted_body, jit_body);
+    assert_eq!(interpreted_body, query);
+    println!(
+        "perf_regex_jit: rules={RULES} keys={KEYS} iterations={iterations} \
+         per-match interpreted={interpreted_match:?} jit={jit_match:?} \
+         delta={:?} baseline p50 interpreted={interpreted_baseline:?} jit={jit_baseline:?}",
+        interpreted_match.saturating_sub(jit_match)
+    );
+}
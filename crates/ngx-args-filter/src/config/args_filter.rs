@@ -1,16 +1,29 @@
 //! `args_filter` configuration structures and evaluation logic
 
+use crate::config::inject::{InjectAction, InjectedArg};
+use crate::config::path::{KeyPath, PathSeparator};
+use crate::config::regex_limits::{RegexExec, RegexLimitExceeded, RegexLimitPolicy, RegexLimits};
+use crate::config::rename::{Rename, RenameSource};
+use crate::config::validate::Validation;
+use crate::config::value_length::ValueLengthLimit;
+use crate::config::zone::KeyZone;
 use crate::nginx_str::NginxStr;
 use crate::status::NgxStatus;
-use ngx::collections::Vec;
-use ngx::core::Pool;
-use ngx::ffi::{NGX_PCRE, NGX_REGEX_CASELESS, ngx_regex_compile_t, ngx_str_t};
-use tracing::{debug, error};
-
-#[cfg(ngx_feature = "pcre2")]
-use ngx::ffi::ngx_regex_exec;
-#[cfg(not(ngx_feature = "pcre2"))]
-use ngx::ffi::pcre_exec;
+use ngx::allocator::AllocError;
+use ngx::collections::rbtree::RbTreeMap;
+use ngx::collections::{TryReserveError, Vec};
+use ngx::core::{NgxStr, Pool};
+use ngx::ffi::{
+    NGX_PCRE, NGX_REGEX_CASELESS, ngx_cacheline_size, ngx_hash_find, ngx_hash_init,
+    ngx_hash_init_t, ngx_hash_key, ngx_hash_key_t, ngx_hash_t, ngx_http_compile_complex_value,
+    ngx_http_compile_complex_value_t, ngx_http_complex_value_t, ngx_pcalloc, ngx_pnalloc,
+    ngx_regex_compile_t, ngx_str_t,
+};
+use ngx::http::Request;
+use std::ffi::c_void;
+use std::fmt;
+use std::ptr::NonNull;
+use tracing::{debug, error, warn};
 
 /// Default behavior when a key does not match any include/exclude rule.
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
@@ -22,6 +35,52 @@ pub enum InitialPolicy {
     All,
 }
 
+/// Which matching rule decides whether a key is kept.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MatchMode {
+    /// Evaluate every rule; the last matching rule wins.
+    #[default]
+    Last,
+    /// Stop at the first matching rule.
+    First,
+}
+
+/// What `decode_keys on` does with a key containing a malformed `%` escape.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum InvalidEscapePolicy {
+    /// Copy malformed escapes unchanged, decode the rest, and match as usual.
+    #[default]
+    Literal,
+    /// Drop the segment without evaluating rules.
+    Drop,
+    /// Keep the segment without evaluating rules.
+    Keep,
+}
+
+/// What `empty_values` does with a segment such as `a=`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum EmptyValuePolicy {
+    #[default]
+    Keep,
+    Drop,
+}
+
+/// What `valueless_keys` does with a segment without `=`, such as `a`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ValuelessKeyPolicy {
+    #[default]
+    Keep,
+    Drop,
+    /// Keep the segment, written as `a=`.
+    AsEmpty,
+}
+
+/// Map of `args_filter` variable names to compiled definitions for one scope.
+pub type ArgsFilterMap = RbTreeMap<NginxStr<Pool>, ArgsFilterDef, Pool>;
+
+/// Map of `args_filter_keys` list names to their compiled matchers.
+pub type KeyListMap = RbTreeMap<NginxStr<Pool>, Vec<RuleMatcher, Pool>, Pool>;
+
 /// Runtime data attached to NGINX variable registration.
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -29,27 +88,118 @@ pub struct ArgsFilterVarData {
     pub name: ngx_str_t,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum RuleAction {
     Include,
     Exclude,
 }
 
-#[derive(Clone, Copy, Debug)]
+impl RuleAction {
+    /// Name of the nested directive that declares rules with this action.
+    pub const fn directive_name(self) -> &'static str {
+        match self {
+            Self::Include => "include",
+            Self::Exclude => "exclude",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct CompiledRegex {
     pub regex: *mut ngx::ffi::ngx_regex_t,
+    /// Source pattern, kept for diagnostics.
+    pub pattern: NginxStr<Pool>,
+    pub case_insensitive: bool,
+    /// Number of capture groups in the pattern.
+    pub captures: usize,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
+pub struct CompiledComplexValue {
+    pub value: *mut ngx_http_complex_value_t,
+}
+
+#[derive(Clone, Debug)]
 pub enum RuleMatcher {
     Literal(NginxStr<Pool>),
+    /// Literal compared with ASCII case folding (`=*`).
+    CaselessLiteral(NginxStr<Pool>),
+    /// Key starts with the pattern (`prefix:`).
+    Prefix(NginxStr<Pool>),
+    /// Key ends with the pattern (`suffix:`).
+    Suffix(NginxStr<Pool>),
+    /// Shell-style pattern where `*` matches any run of bytes and `?` one byte (`glob:`).
+    Glob(NginxStr<Pool>),
+    /// Nested parameter name compared component by component (`path:`).
+    Path(KeyPath),
     Regex(CompiledRegex),
+    /// Literal operand containing variables, evaluated once per request.
+    Complex(CompiledComplexValue),
+    /// Key set kept in an `args_filter_zone`, looked up on every match.
+    Zone(KeyZone),
+    /// Every key; used by `exclude_value`, where only the value pattern decides.
+    Any,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Rule {
     pub action: RuleAction,
     pub matcher: RuleMatcher,
+    /// Optional `value~`/`value~*` pattern the percent-decoded value must also match.
+    pub value: Option<CompiledRegex>,
+    /// Optional `if=` condition; the rule is skipped when it evaluates to "" or "0".
+    pub condition: Option<CompiledComplexValue>,
+}
+
+/// Adjacent literal rules worth indexing; shorter runs are scanned.
+const LITERAL_RUN_MIN: usize = 8;
+
+/// Adjacent regex rules worth combining into one alternation.
+const REGEX_RUN_MIN: usize = 2;
+
+/// Adjacent unconditional literal and regex rules with the same action,
+/// evaluated together so that one hash lookup and one regex execution replace
+/// scanning them.
+#[derive(Debug)]
+pub struct RuleRun {
+    /// Index of the first rule of the run in `rules`.
+    pub start: usize,
+    /// Index one past the last rule of the run.
+    pub end: usize,
+    /// nginx hash of the run's literal keys; `None` when the run has none.
+    pub keys: Option<ngx_hash_t>,
+    /// Alternation of the run's regexes, followed by any regex that could not
+    /// be combined.
+    pub regexes: Vec<CompiledRegex, Pool>,
+}
+
+impl RuleRun {
+    /// Return true when some rule of the run matches `key`.
+    fn matches(&self, key: &[u8], limits: &RegexLimits) -> Result<bool, RegexLimitExceeded> {
+        if self
+            .keys
+            .as_ref()
+            .is_some_and(|keys| hash_contains(keys, key))
+        {
+            return Ok(true);
+        }
+
+        for regex in &self.regexes {
+            if regex_matches(regex, key, limits)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Per-request state of one rule, produced by [`ArgsFilterDef::evaluate_dynamic`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RuleState<'r> {
+    /// Evaluated operand of a complex-value rule.
+    pub operand: Option<&'r [u8]>,
+    /// True when the rule's `if=` condition is false for this request.
+    pub disabled: bool,
 }
 
 /// Full configuration for one `args_filter` variable.
@@ -57,9 +207,52 @@ pub struct Rule {
 pub struct ArgsFilterDef {
     pub initial: InitialPolicy,
     pub initial_set: bool,
+    pub match_mode: MatchMode,
+    pub match_set: bool,
+    /// Match rules against percent-decoded keys (`decode_keys on`).
+    pub decode_keys: bool,
+    pub decode_keys_set: bool,
+    pub invalid_escapes: InvalidEscapePolicy,
+    /// Match `ids[]`, `ids[0]`, and their percent-encoded forms as `ids`.
+    pub array_keys: bool,
+    pub array_keys_set: bool,
+    /// Separators used to split keys and patterns of later `path:` rules.
+    pub path_separator: PathSeparator,
+    pub path_separator_set: bool,
+    pub empty_values: EmptyValuePolicy,
+    pub empty_values_set: bool,
+    pub valueless_keys: ValuelessKeyPolicy,
+    pub valueless_keys_set: bool,
+    /// PCRE limits for rule regexes and what a hit limit does to the argument.
+    pub regex_limits: RegexLimits,
+    pub regex_match_limit_set: bool,
+    pub regex_depth_limit_set: bool,
+    pub regex_limit_exceeded_set: bool,
     /// If true, mark the exposed nginx variable as non-cacheable.
     pub volatile: bool,
+    /// Name of the filter this one was derived from with `extends`.
+    pub extends: Option<NginxStr<Pool>>,
+    /// Number of leading `rules` copied from the `extends` base.
+    pub inherited_rules: usize,
     pub rules: Option<Vec<Rule, Pool>>,
+    /// Rule runs in `rules`, in rule order; built by [`Self::index_rule_runs`].
+    pub rule_runs: Option<Vec<RuleRun, Pool>>,
+    /// `validate` directives, at most one per key.
+    pub validations: Option<Vec<Validation, Pool>>,
+    /// Number of leading `validations` copied from the `extends` base.
+    pub inherited_validations: usize,
+    /// `max_value_length` directives: at most one filter-wide limit and one per key.
+    pub value_length_limits: Option<Vec<ValueLengthLimit, Pool>>,
+    /// Number of leading `value_length_limits` copied from the `extends` base.
+    pub inherited_value_length_limits: usize,
+    /// `rename` directives, in declaration order.
+    pub renames: Option<Vec<Rename, Pool>>,
+    /// Number of leading `renames` copied from the `extends` base.
+    pub inherited_renames: usize,
+    /// `set` and `add` directives, in declaration order.
+    pub injected_args: Option<Vec<InjectedArg, Pool>>,
+    /// Number of leading `injected_args` copied from the `extends` base.
+    pub inherited_injected_args: usize,
 }
 
 impl ArgsFilterDef {
@@ -68,14 +261,128 @@ impl ArgsFilterDef {
         Self {
             initial: InitialPolicy::None,
             initial_set: false,
+            match_mode: MatchMode::Last,
+            match_set: false,
+            decode_keys: false,
+            decode_keys_set: false,
+            invalid_escapes: InvalidEscapePolicy::Literal,
+            array_keys: false,
+            array_keys_set: false,
+            path_separator: PathSeparator::Both,
+            path_separator_set: false,
+            empty_values: EmptyValuePolicy::Keep,
+            empty_values_set: false,
+            valueless_keys: ValuelessKeyPolicy::Keep,
+            valueless_keys_set: false,
+            regex_limits: RegexLimits::new(),
+            regex_match_limit_set: false,
+            regex_depth_limit_set: false,
+            regex_limit_exceeded_set: false,
             volatile: false,
+            extends: None,
+            inherited_rules: 0,
             rules: None,
+            rule_runs: None,
+            validations: None,
+            inherited_validations: 0,
+            value_length_limits: None,
+            inherited_value_length_limits: 0,
+            renames: None,
+            inherited_renames: 0,
+            injected_args: None,
+            inherited_injected_args: 0,
+        }
+    }
+
+    /// Evaluate complex-value rule operands and `if=` conditions for one request.
+    ///
+    /// The result is indexed like `rules` and is empty when the filter has no
+    /// per-request rule state.
+    pub fn evaluate_dynamic<'r>(&self, req: &'r Request) -> std::vec::Vec<RuleState<'r>> {
+        let Some(rules) = self.rules.as_ref() else {
+            return std::vec::Vec::new();
+        };
+
+        if !rules
+            .iter()
+            .any(|rule| rule.condition.is_some() || matches!(rule.matcher, RuleMatcher::Complex(_)))
+        {
+            return std::vec::Vec::new();
         }
+
+        rules
+            .iter()
+            .map(|rule| RuleState {
+                operand: match &rule.matcher {
+                    RuleMatcher::Complex(cv) => evaluate_complex_value(req, *cv),
+                    RuleMatcher::Literal(_)
+                    | RuleMatcher::CaselessLiteral(_)
+                    | RuleMatcher::Prefix(_)
+                    | RuleMatcher::Suffix(_)
+                    | RuleMatcher::Glob(_)
+                    | RuleMatcher::Path(_)
+                    | RuleMatcher::Regex(_)
+                    | RuleMatcher::Zone(_)
+                    | RuleMatcher::Any => None,
+                },
+                disabled: rule
+                    .condition
+                    .is_some_and(|cv| !condition_holds(evaluate_complex_value(req, cv))),
+            })
+            .collect()
     }
 
-    /// Return true when `key` should be kept.
-    /// Rules are evaluated in declaration order.
-    pub fn should_keep_key(&self, key: &[u8]) -> bool {
+    /// Return true when any rule is a `path:` rule.
+    pub fn has_path_rules(&self) -> bool {
+        self.rules.as_ref().is_some_and(|rules| {
+            rules
+                .iter()
+                .any(|rule| matches!(rule.matcher, RuleMatcher::Path(_)))
+        })
+    }
+
+    /// Return true when any rule also matches on the argument value.
+    pub fn has_value_rules(&self) -> bool {
+        self.rules
+            .as_ref()
+            .is_some_and(|rules| rules.iter().any(|rule| rule.value.is_some()))
+    }
+
+    /// Return true when the segment with `key` and `value` should be kept.
+    /// Rules are evaluated in declaration order until the deciding match
+    /// (see [`MatchMode`]); `dynamic` holds the state
+    /// returned by [`Self::evaluate_dynamic`] for the current request.
+    ///
+    /// When a rule regex stops at a PCRE limit, `regex_limit_exceeded` decides:
+    /// the segment is kept, dropped, or the error is returned.
+    pub fn should_keep_key(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        dynamic: &[RuleState<'_>],
+    ) -> Result<bool, RegexLimitExceeded> {
+        self.evaluate_rules(key, value, dynamic).or_else(|limit| {
+            let policy = self.regex_limits.exceeded;
+            warn!(
+                "args_filter: key='{}' stopped at a regex limit; policy={}",
+                String::from_utf8_lossy(key),
+                policy.name()
+            );
+
+            match policy {
+                RegexLimitPolicy::Keep => Ok(true),
+                RegexLimitPolicy::Drop => Ok(false),
+                RegexLimitPolicy::Fail => Err(limit),
+            }
+        })
+    }
+
+    fn evaluate_rules(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        dynamic: &[RuleState<'_>],
+    ) -> Result<bool, RegexLimitExceeded> {
         let mut keep = self.initial == InitialPolicy::All;
         let key_text = String::from_utf8_lossy(key);
 
@@ -84,7 +391,7 @@ impl ArgsFilterDef {
                 "args_filter: key='{}' no rules configured; keep={}",
                 key_text, keep
             );
-            return keep;
+            return Ok(keep);
         };
 
         debug!(
@@ -94,15 +401,52 @@ impl ArgsFilterDef {
             keep
         );
 
-        for (idx, rule) in rules.iter().enumerate() {
-            if !rule.matches(key) {
-                debug!(
-                    "args_filter: key='{}' rule[{}] {} did not match",
-                    key_text,
-                    idx,
-                    rule.debug_label()
-                );
-                continue;
+        let mut runs = self
+            .rule_runs
+            .as_ref()
+            .map_or(&[][..], ngx::collections::Vec::as_slice)
+            .iter()
+            .peekable();
+        let mut idx = 0;
+
+        while let Some(rule) = rules.get(idx) {
+            let rule_idx = idx;
+
+            if let Some(run) = runs.next_if(|run| run.start == rule_idx) {
+                idx = run.end;
+                if !run.matches(key, &self.regex_limits)? {
+                    debug!(
+                        "args_filter: key='{}' rule[{}..{}] {} run did not match",
+                        key_text,
+                        run.start,
+                        run.end,
+                        rule.debug_label()
+                    );
+                    continue;
+                }
+            } else {
+                idx += 1;
+
+                let state = dynamic.get(rule_idx).copied().unwrap_or_default();
+                if state.disabled {
+                    debug!(
+                        "args_filter: key='{}' rule[{}] {} skipped by condition",
+                        key_text,
+                        rule_idx,
+                        rule.debug_label()
+                    );
+                    continue;
+                }
+
+                if !rule.matches(key, value, state.operand, &self.regex_limits)? {
+                    debug!(
+                        "args_filter: key='{}' rule[{}] {} did not match",
+                        key_text,
+                        rule_idx,
+                        rule.debug_label()
+                    );
+                    continue;
+                }
             }
 
             keep = match rule.action {
@@ -112,14 +456,72 @@ impl ArgsFilterDef {
             debug!(
                 "args_filter: key='{}' rule[{}] {} matched; keep={}",
                 key_text,
-                idx,
+                rule_idx,
                 rule.debug_label(),
                 keep
             );
+
+            if self.match_mode == MatchMode::First {
+                break;
+            }
         }
 
         debug!("args_filter: key='{}' final keep={}", key_text, keep);
-        keep
+        Ok(keep)
+    }
+
+    /// Index runs of adjacent unconditional literal and regex rules with the
+    /// same action.
+    ///
+    /// Within such a run the only question is whether some rule matches the
+    /// key, so the declared order of rules, and thus which match wins, is kept.
+    /// The run's regexes are compiled into one alternation; a regex whose
+    /// meaning depends on the rest of the pattern stays separate.
+    pub fn index_rule_runs(&mut self, cf: *mut ngx::ffi::ngx_conf_t) -> Result<(), AllocError> {
+        let Some(rules) = self.rules.as_ref() else {
+            return Ok(());
+        };
+
+        let pool = unsafe { Pool::from_ngx_pool((*cf).pool) };
+        let mut runs = Vec::new_in(pool.clone());
+        let mut start = 0;
+
+        while let Some(first) = rules.get(start) {
+            let len = rules[start..]
+                .iter()
+                .take_while(|rule| rule.action == first.action && rule.is_plain())
+                .count();
+            let end = start + len.max(1);
+            let run = &rules[start..end];
+
+            let literals = run
+                .iter()
+                .filter(|rule| matches!(rule.matcher, RuleMatcher::Literal(_)))
+                .count();
+            if len > 0 && (literals >= LITERAL_RUN_MIN || len - literals >= REGEX_RUN_MIN) {
+                let keys = hash_literal_keys(cf, run)?;
+                let mut regexes = Vec::new_in(pool.clone());
+                for rule in run {
+                    if let RuleMatcher::Regex(regex) = &rule.matcher {
+                        regexes.push(regex.clone());
+                    }
+                }
+                combine_regexes(cf, &mut regexes);
+                runs.push(RuleRun {
+                    start,
+                    end,
+                    keys,
+                    regexes,
+                });
+            }
+
+            start = end;
+        }
+
+        if !runs.is_empty() {
+            self.rule_runs = Some(runs);
+        }
+        Ok(())
     }
 
     /// Returns true when output is always identical to input query args.
@@ -129,77 +531,522 @@ impl ArgsFilterDef {
                 .rules
                 .as_ref()
                 .is_none_or(ngx::collections::Vec::is_empty)
+            && self.validations.is_none()
+            && self.value_length_limits.is_none()
+            && self.renames.is_none()
+            && self.injected_args.is_none()
+            && !(self.decode_keys && self.invalid_escapes == InvalidEscapePolicy::Drop)
+            && self.empty_values == EmptyValuePolicy::Keep
+            && self.valueless_keys == ValuelessKeyPolicy::Keep
+    }
+
+    /// Return the `validate` directive for the normalized `key`, if any.
+    pub fn find_validation(&self, key: &[u8]) -> Option<&Validation> {
+        self.validations
+            .as_ref()?
+            .iter()
+            .find(|validation| validation.key.as_bytes() == key)
+    }
+
+    /// Return the `max_value_length` limit for the normalized `key`: its own
+    /// limit if declared, otherwise the filter-wide one.
+    pub fn find_value_length_limit(&self, key: &[u8]) -> Option<&ValueLengthLimit> {
+        let limits = self.value_length_limits.as_ref()?;
+
+        limits
+            .iter()
+            .find(|limit| limit.key.as_ref().is_some_and(|k| k.as_bytes() == key))
+            .or_else(|| limits.iter().find(|limit| limit.key.is_none()))
+    }
+
+    pub fn add_value_length_limit(&mut self, pool: Pool, limit: ValueLengthLimit) {
+        if self.value_length_limits.is_none() {
+            self.value_length_limits = Some(Vec::new_in(pool));
+        }
+
+        if let Some(limits) = self.value_length_limits.as_mut() {
+            // A limit declared in this block replaces the inherited one.
+            let key = limit.key.as_ref().map(NginxStr::as_bytes);
+            drop_inherited(limits, &mut self.inherited_value_length_limits, |old| {
+                old.key.as_ref().map(NginxStr::as_bytes) == key
+            });
+            limits.push(limit);
+        }
+    }
+
+    /// Append to `out` the new name of the normalized `key` from the first
+    /// matching `rename`; returns false when none matches. Captured text is
+    /// appended through `push_capture`.
+    pub fn rename_key(
+        &self,
+        key: &[u8],
+        out: &mut std::vec::Vec<u8>,
+        push_capture: impl Fn(&mut std::vec::Vec<u8>, &[u8]),
+    ) -> Result<bool, RegexLimitExceeded> {
+        for rename in self.renames.iter().flatten() {
+            if rename.apply(key, &self.regex_limits, out, &push_capture)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
-    pub fn add_include_literal(&mut self, pool: Pool, key: NginxStr<Pool>) {
-        self.push_rule(pool, RuleAction::Include, RuleMatcher::Literal(key));
+    /// Return true when a literal `rename` declared in this block already
+    /// matches `key`.
+    pub fn has_literal_rename(&self, key: &[u8]) -> bool {
+        local(self.renames.as_ref(), self.inherited_renames)
+            .iter()
+            .any(|rename| {
+                matches!(&rename.source, RenameSource::Literal(from) if from.as_bytes() == key)
+            })
     }
 
-    pub fn add_exclude_literal(&mut self, pool: Pool, key: NginxStr<Pool>) {
-        self.push_rule(pool, RuleAction::Exclude, RuleMatcher::Literal(key));
+    /// Return true when this block already declares `validate` for `key`.
+    pub fn has_local_validation(&self, key: &[u8]) -> bool {
+        local(self.validations.as_ref(), self.inherited_validations)
+            .iter()
+            .any(|validation| validation.key.as_bytes() == key)
+    }
+
+    /// Return true when this block already declares a `max_value_length` for
+    /// `key`, or a filter-wide one when `key` is `None`.
+    pub fn has_local_value_length_limit(&self, key: Option<&[u8]>) -> bool {
+        local(
+            self.value_length_limits.as_ref(),
+            self.inherited_value_length_limits,
+        )
+        .iter()
+        .any(|limit| limit.key.as_ref().map(NginxStr::as_bytes) == key)
+    }
+
+    /// Return true when this block already declares `set` for `key`.
+    pub fn has_local_set_arg(&self, key: &[u8]) -> bool {
+        local(self.injected_args.as_ref(), self.inherited_injected_args)
+            .iter()
+            .any(|arg| arg.action == InjectAction::Set && arg.key.as_bytes() == key)
     }
 
-    pub fn add_include_regex(
+    /// Return the `set` directives, whose keys replace kept occurrences.
+    pub fn set_args(&self) -> impl Iterator<Item = &InjectedArg> {
+        self.injected_args
+            .iter()
+            .flatten()
+            .filter(|arg| arg.action == InjectAction::Set)
+    }
+
+    pub fn add_injected_arg(&mut self, pool: Pool, arg: InjectedArg) {
+        if self.injected_args.is_none() {
+            self.injected_args = Some(Vec::new_in(pool));
+        }
+
+        if let Some(args) = self.injected_args.as_mut() {
+            if arg.action == InjectAction::Set {
+                drop_inherited(args, &mut self.inherited_injected_args, |old| {
+                    old.action == InjectAction::Set && old.key.as_bytes() == arg.key.as_bytes()
+                });
+            }
+            args.push(arg);
+        }
+    }
+
+    pub fn add_rename(&mut self, pool: Pool, rename: Rename) {
+        if self.renames.is_none() {
+            self.renames = Some(Vec::new_in(pool));
+        }
+
+        if let Some(renames) = self.renames.as_mut() {
+            if let RenameSource::Literal(from) = &rename.source {
+                drop_inherited(
+                    renames,
+                    &mut self.inherited_renames,
+                    |old| matches!(&old.source, RenameSource::Literal(old_from) if old_from.as_bytes() == from.as_bytes()),
+                );
+            }
+            renames.push(rename);
+        }
+    }
+
+    pub fn add_validation(&mut self, pool: Pool, validation: Validation) {
+        if self.validations.is_none() {
+            self.validations = Some(Vec::new_in(pool));
+        }
+
+        if let Some(validations) = self.validations.as_mut() {
+            drop_inherited(validations, &mut self.inherited_validations, |old| {
+                old.key.as_bytes() == validation.key.as_bytes()
+            });
+            validations.push(validation);
+        }
+    }
+
+    /// Copy the `initial` policy, match mode, key normalization, empty value
+    /// policies, regex limits, validations, value length limits, renames,
+    /// injected arguments, and rules of `base` into this filter.
+    pub fn extend_from(
         &mut self,
-        cf: *mut ngx::ffi::ngx_conf_t,
-        pattern: ngx_str_t,
-        case_insensitive: bool,
-    ) -> Result<(), ()> {
-        let regex = compile_regex(cf, pattern, case_insensitive)?;
-        let pool = unsafe { Pool::from_ngx_pool((*cf).pool) };
-        self.push_rule(pool, RuleAction::Include, RuleMatcher::Regex(regex));
+        pool: Pool,
+        base_name: NginxStr<Pool>,
+        base: &Self,
+    ) -> Result<(), TryReserveError> {
+        self.initial = base.initial;
+        self.match_mode = base.match_mode;
+        self.decode_keys = base.decode_keys;
+        self.invalid_escapes = base.invalid_escapes;
+        self.array_keys = base.array_keys;
+        self.path_separator = base.path_separator;
+        self.empty_values = base.empty_values;
+        self.valueless_keys = base.valueless_keys;
+        self.regex_limits.copy_settings(&base.regex_limits);
+        self.extends = Some(base_name);
+
+        if let Some(base_validations) = base.validations.as_ref() {
+            let mut validations = Vec::new_in(pool.clone());
+            validations.try_reserve_exact(base_validations.len())?;
+            validations.extend(base_validations.iter().cloned());
+            self.inherited_validations = validations.len();
+            self.validations = Some(validations);
+        }
+
+        if let Some(base_limits) = base.value_length_limits.as_ref() {
+            let mut limits = Vec::new_in(pool.clone());
+            limits.try_reserve_exact(base_limits.len())?;
+            limits.extend(base_limits.iter().cloned());
+            self.inherited_value_length_limits = limits.len();
+            self.value_length_limits = Some(limits);
+        }
+
+        if let Some(base_renames) = base.renames.as_ref() {
+            let mut renames = Vec::new_in(pool.clone());
+            renames.try_reserve_exact(base_renames.len())?;
+            renames.extend(base_renames.iter().cloned());
+            self.inherited_renames = renames.len();
+            self.renames = Some(renames);
+        }
+
+        if let Some(base_args) = base.injected_args.as_ref() {
+            let mut args = Vec::new_in(pool.clone());
+            args.try_reserve_exact(base_args.len())?;
+            args.extend(base_args.iter().cloned());
+            self.inherited_injected_args = args.len();
+            self.injected_args = Some(args);
+        }
+
+        let Some(base_rules) = base.rules.as_ref() else {
+            return Ok(());
+        };
+
+        let mut rules = Vec::new_in(pool);
+        rules.try_reserve_exact(base_rules.len())?;
+        rules.extend(base_rules.iter().cloned());
+        self.inherited_rules = rules.len();
+        self.rules = Some(rules);
         Ok(())
     }
 
-    pub fn add_exclude_regex(
+    /// Append one rule per matcher of an `args_filter_keys` list.
+    pub fn add_key_list(
         &mut self,
-        cf: *mut ngx::ffi::ngx_conf_t,
-        pattern: ngx_str_t,
-        case_insensitive: bool,
-    ) -> Result<(), ()> {
-        let regex = compile_regex(cf, pattern, case_insensitive)?;
-        let pool = unsafe { Pool::from_ngx_pool((*cf).pool) };
-        self.push_rule(pool, RuleAction::Exclude, RuleMatcher::Regex(regex));
-        Ok(())
+        pool: Pool,
+        action: RuleAction,
+        matchers: &[RuleMatcher],
+        value: Option<&CompiledRegex>,
+        condition: Option<CompiledComplexValue>,
+    ) {
+        for matcher in matchers {
+            self.add_rule(
+                pool.clone(),
+                action,
+                matcher.clone(),
+                value.cloned(),
+                condition,
+            );
+        }
     }
 
-    fn push_rule(&mut self, pool: Pool, action: RuleAction, matcher: RuleMatcher) {
+    pub fn add_rule(
+        &mut self,
+        pool: Pool,
+        action: RuleAction,
+        matcher: RuleMatcher,
+        value: Option<CompiledRegex>,
+        condition: Option<CompiledComplexValue>,
+    ) {
         if self.rules.is_none() {
             self.rules = Some(Vec::new_in(pool));
         }
 
         if let Some(rules) = self.rules.as_mut() {
-            rules.push(Rule { action, matcher });
+            rules.push(Rule {
+                action,
+                matcher,
+                value,
+                condition,
+            });
         }
     }
 }
 
 impl Rule {
-    fn matches(&self, key: &[u8]) -> bool {
-        match &self.matcher {
-            RuleMatcher::Literal(expected) => expected.as_bytes() == key,
-            RuleMatcher::Regex(regex) => regex_matches(regex, key),
+    /// Return true when both the key matcher and the optional value pattern match.
+    pub fn matches(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        dynamic: Option<&[u8]>,
+        limits: &RegexLimits,
+    ) -> Result<bool, RegexLimitExceeded> {
+        if !self.matches_key(key, dynamic, limits)? {
+            return Ok(false);
         }
+
+        self.value
+            .as_ref()
+            .map_or(Ok(true), |regex| regex_matches(regex, value, limits))
+    }
+
+    /// True for an exact literal or regex rule without `if=` or a value pattern.
+    const fn is_plain(&self) -> bool {
+        matches!(
+            self.matcher,
+            RuleMatcher::Literal(_) | RuleMatcher::Regex(_)
+        ) && self.condition.is_none()
+            && self.value.is_none()
+    }
+
+    pub fn matches_key(
+        &self,
+        key: &[u8],
+        dynamic: Option<&[u8]>,
+        limits: &RegexLimits,
+    ) -> Result<bool, RegexLimitExceeded> {
+        let matched = match &self.matcher {
+            RuleMatcher::Literal(expected) => expected.as_bytes() == key,
+            RuleMatcher::CaselessLiteral(expected) => expected.as_bytes().eq_ignore_ascii_case(key),
+            RuleMatcher::Prefix(prefix) => key.starts_with(prefix.as_bytes()),
+            RuleMatcher::Suffix(suffix) => key.ends_with(suffix.as_bytes()),
+            RuleMatcher::Glob(pattern) => glob_matches(pattern.as_bytes(), key),
+            RuleMatcher::Path(path) => path.matches(key),
+            RuleMatcher::Regex(regex) => return regex_matches(regex, key, limits),
+            // An empty evaluated operand never matches, so unset variables are no-ops.
+            RuleMatcher::Complex(_) => dynamic.is_some_and(|v| !v.is_empty() && v == key),
+            RuleMatcher::Zone(zone) => zone.contains(key),
+            RuleMatcher::Any => true,
+        };
+        Ok(matched)
     }
 
     const fn debug_label(&self) -> &'static str {
         match (self.action, &self.matcher) {
             (RuleAction::Include, RuleMatcher::Literal(_)) => "include literal",
             (RuleAction::Exclude, RuleMatcher::Literal(_)) => "exclude literal",
+            (RuleAction::Include, RuleMatcher::CaselessLiteral(_)) => "include caseless literal",
+            (RuleAction::Exclude, RuleMatcher::CaselessLiteral(_)) => "exclude caseless literal",
+            (RuleAction::Include, RuleMatcher::Prefix(_)) => "include prefix",
+            (RuleAction::Exclude, RuleMatcher::Prefix(_)) => "exclude prefix",
+            (RuleAction::Include, RuleMatcher::Suffix(_)) => "include suffix",
+            (RuleAction::Exclude, RuleMatcher::Suffix(_)) => "exclude suffix",
+            (RuleAction::Include, RuleMatcher::Glob(_)) => "include glob",
+            (RuleAction::Exclude, RuleMatcher::Glob(_)) => "exclude glob",
+            (RuleAction::Include, RuleMatcher::Path(_)) => "include path",
+            (RuleAction::Exclude, RuleMatcher::Path(_)) => "exclude path",
             (RuleAction::Include, RuleMatcher::Regex(_)) => "include regex",
             (RuleAction::Exclude, RuleMatcher::Regex(_)) => "exclude regex",
+            (RuleAction::Include, RuleMatcher::Complex(_)) => "include variable",
+            (RuleAction::Exclude, RuleMatcher::Complex(_)) => "exclude variable",
+            (RuleAction::Include, RuleMatcher::Zone(_)) => "include zone",
+            (RuleAction::Exclude, RuleMatcher::Zone(_)) => "exclude zone",
+            (RuleAction::Include, RuleMatcher::Any) => "include value",
+            (RuleAction::Exclude, RuleMatcher::Any) => "exclude value",
+        }
+    }
+}
+
+impl RuleMatcher {
+    /// Short name of the matcher type, as reported by `args_filter_dump`.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Literal(_) | Self::CaselessLiteral(_) => "literal",
+            Self::Prefix(_) => "prefix",
+            Self::Suffix(_) => "suffix",
+            Self::Glob(_) => "glob",
+            Self::Path(_) => "path",
+            Self::Regex(_) => "regex",
+            Self::Complex(_) => "variable",
+            Self::Zone(_) => "zone",
+            Self::Any => "any",
+        }
+    }
+
+    /// Operand text as written in the configuration.
+    pub fn source(&self) -> String {
+        match self {
+            Self::Literal(key)
+            | Self::CaselessLiteral(key)
+            | Self::Prefix(key)
+            | Self::Suffix(key)
+            | Self::Glob(key)
+            | Self::Path(KeyPath { pattern: key, .. }) => key.to_string(),
+            Self::Regex(regex) => regex.pattern.to_string(),
+            Self::Complex(cv) => complex_value_source(*cv),
+            Self::Zone(zone) => zone.name(),
+            Self::Any => String::new(),
         }
     }
+
+    pub const fn case_insensitive(&self) -> bool {
+        match self {
+            Self::CaselessLiteral(_) => true,
+            Self::Regex(regex) => regex.case_insensitive,
+            Self::Literal(_)
+            | Self::Prefix(_)
+            | Self::Suffix(_)
+            | Self::Glob(_)
+            | Self::Path(_)
+            | Self::Complex(_)
+            | Self::Zone(_)
+            | Self::Any => false,
+        }
+    }
+}
+
+impl Rule {
+    /// `if=` condition text as written in the configuration.
+    pub fn condition_source(&self) -> Option<String> {
+        self.condition.map(complex_value_source)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.matcher, &self.value) {
+            (RuleMatcher::Any, Some(value)) => {
+                write!(f, "{}_value {value}", self.action.directive_name())?;
+            }
+            (matcher, value) => {
+                write!(f, "{} {matcher}", self.action.directive_name())?;
+                if let Some(value) = value {
+                    write!(f, " value{value}")?;
+                }
+            }
+        }
+        if let Some(condition) = self.condition_source() {
+            write!(f, " if={condition}")?;
+        }
+        Ok(())
+    }
 }
 
-fn compile_regex(
+impl fmt::Display for RuleMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Literal(_) | Self::Complex(_) => write!(f, "\"{}\"", self.source()),
+            Self::CaselessLiteral(_) => write!(f, "=* \"{}\"", self.source()),
+            Self::Prefix(_) | Self::Suffix(_) | Self::Glob(_) | Self::Path(_) => {
+                write!(f, "\"{}:{}\"", self.kind(), self.source())
+            }
+            Self::Regex(regex) => write!(f, "{regex}"),
+            Self::Zone(_) => write!(f, "@zone:{}", self.source()),
+            Self::Any => f.write_str("*"),
+        }
+    }
+}
+
+impl fmt::Display for CompiledRegex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = if self.case_insensitive { "~*" } else { "~" };
+        write!(f, "{operator} \"{}\"", self.pattern)
+    }
+}
+
+pub fn complex_value_source(cv: CompiledComplexValue) -> String {
+    let value = unsafe { (*cv.value).value };
+    String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(value.data, value.len) })
+        .into_owned()
+}
+
+/// Compile a single `prefix:`, `suffix:`, `glob:`, or `path:` operand.
+///
+/// Returns `Ok(None)` when the operand has none of these prefixes. `path:`
+/// patterns are split with `separator`.
+pub fn compile_pattern_matcher(
+    cf: &ngx::ffi::ngx_conf_t,
+    operand: &ngx_str_t,
+    separator: PathSeparator,
+) -> Result<Option<RuleMatcher>, ()> {
+    const KINDS: [&[u8]; 4] = [b"prefix:", b"suffix:", b"glob:", b"path:"];
+
+    let value = unsafe { std::slice::from_raw_parts(operand.data, operand.len) };
+    let Some((kind, pattern)) = KINDS
+        .iter()
+        .find_map(|kind| Some((*kind, value.strip_prefix(*kind)?)))
+    else {
+        return Ok(None);
+    };
+
+    if pattern.is_empty() {
+        error!(
+            r#""{}" requires a non-empty pattern"#,
+            String::from_utf8_lossy(value)
+        );
+        return Err(());
+    }
+
+    let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+    let Ok(pattern) = NginxStr::from_bytes(pool, pattern) else {
+        error!("failed to allocate key pattern");
+        return Err(());
+    };
+
+    Ok(Some(match kind {
+        b"prefix:" => RuleMatcher::Prefix(pattern),
+        b"suffix:" => RuleMatcher::Suffix(pattern),
+        b"glob:" => RuleMatcher::Glob(pattern),
+        _ => RuleMatcher::Path(KeyPath { pattern, separator }),
+    }))
+}
+
+/// Compile the two-operand rule form `<operator> <operand>` (`=*`, `~`, or `~*`).
+///
+/// Returns `Ok(None)` for an unknown operator so callers can report it in
+/// their own directive's terms.
+pub fn compile_operator_matcher(
+    cf: *mut ngx::ffi::ngx_conf_t,
+    operator: ngx_str_t,
+    operand: ngx_str_t,
+) -> Result<Option<RuleMatcher>, ()> {
+    match unsafe { std::slice::from_raw_parts(operator.data, operator.len) } {
+        b"=*" => {
+            let Ok(key) = NginxStr::from_ngx_str(unsafe { &*cf }, &operand) else {
+                error!("failed to allocate literal key");
+                return Err(());
+            };
+            Ok(Some(RuleMatcher::CaselessLiteral(key)))
+        }
+        b"~" => compile_regex(cf, operand, false).map(|regex| Some(RuleMatcher::Regex(regex))),
+        b"~*" => compile_regex(cf, operand, true).map(|regex| Some(RuleMatcher::Regex(regex))),
+        _ => Ok(None),
+    }
+}
+
+pub fn compile_regex(
     cf: *mut ngx::ffi::ngx_conf_t,
     pattern: ngx_str_t,
     case_insensitive: bool,
 ) -> Result<CompiledRegex, ()> {
+    try_compile_regex(cf, pattern, case_insensitive).map_err(|err| error!("{}", err))
+}
+
+/// Compile `pattern`, returning the error message instead of logging it.
+///
+/// Regexes compiled while the configuration is read are queued by nginx and
+/// JIT-compiled at startup under `pcre_jit on;`, so no JIT option is passed here.
+fn try_compile_regex(
+    cf: *mut ngx::ffi::ngx_conf_t,
+    pattern: ngx_str_t,
+    case_insensitive: bool,
+) -> Result<CompiledRegex, String> {
     if NGX_PCRE == 0 {
-        error!("regex rules require NGINX with PCRE/PCRE2 support");
-        return Err(());
+        return Err("regex rules require NGINX with PCRE/PCRE2 support".to_owned());
     }
 
     let mut err_buf = [0u8; 256];
@@ -222,51 +1069,340 @@ fn compile_regex(
             .position(|b| *b == 0)
             .unwrap_or(err_buf.len());
         let err_text = String::from_utf8_lossy(&err_buf[..err_len]);
-        error!("failed to compile regex: {}", err_text);
-        return Err(());
+        return Err(format!("failed to compile regex: {err_text}"));
     }
 
     if rc.regex.is_null() {
-        error!("failed to compile regex: empty compiled regex pointer");
-        return Err(());
+        return Err("failed to compile regex: empty compiled regex pointer".to_owned());
     }
 
-    Ok(CompiledRegex { regex: rc.regex })
+    let Ok(pattern) = NginxStr::from_ngx_str(unsafe { &*cf }, &pattern) else {
+        return Err("failed to allocate regex pattern".to_owned());
+    };
+
+    Ok(CompiledRegex {
+        regex: rc.regex,
+        pattern,
+        case_insensitive,
+        captures: usize::try_from(rc.captures).unwrap_or(0),
+    })
 }
 
-fn regex_matches(regex: &CompiledRegex, key: &[u8]) -> bool {
-    let key_ngx = ngx_str_t {
-        len: key.len(),
-        data: key.as_ptr().cast_mut(),
-    };
+/// Entries declared in the block itself, after the `inherited` ones copied by
+/// `extends`.
+fn local<T>(entries: Option<&Vec<T, Pool>>, inherited: usize) -> &[T] {
+    entries.map_or(&[], |entries| &entries[inherited..])
+}
+
+/// Remove the inherited entry selected by `is_replaced`, so that an entry
+/// declared in the block takes its place.
+fn drop_inherited<T>(
+    entries: &mut Vec<T, Pool>,
+    inherited: &mut usize,
+    is_replaced: impl Fn(&T) -> bool,
+) {
+    if let Some(idx) = entries[..*inherited].iter().position(is_replaced) {
+        entries.remove(idx);
+        *inherited -= 1;
+    }
+}
+
+/// Build an nginx hash of the literal keys of `run`, or `None` when it has no
+/// literals.
+fn hash_literal_keys(
+    cf: *mut ngx::ffi::ngx_conf_t,
+    run: &[Rule],
+) -> Result<Option<ngx_hash_t>, AllocError> {
+    // Only the presence of a key matters, so every key maps to the same
+    // non-null value.
+    let value = NonNull::<c_void>::dangling().as_ptr();
+    let mut names: std::vec::Vec<ngx_hash_key_t> = run
+        .iter()
+        .filter_map(|rule| match &rule.matcher {
+            RuleMatcher::Literal(key) => Some(ngx_hash_key_t {
+                key: ngx_str_t {
+                    len: key.as_bytes().len(),
+                    data: key.as_bytes().as_ptr().cast_mut(),
+                },
+                key_hash: unsafe {
+                    ngx_hash_key(key.as_bytes().as_ptr().cast_mut(), key.as_bytes().len())
+                },
+                value,
+            }),
+            _ => None,
+        })
+        .collect();
+    if names.is_empty() {
+        return Ok(None);
+    }
+
+    // Size buckets for four of the longest keys, as NGX_HASH_ELT_SIZE counts
+    // them, and allow up to four slots per key to find a table that fits.
+    let word = core::mem::size_of::<*mut c_void>();
+    let longest = names
+        .iter()
+        .map(|name| word + (name.key.len + 2).next_multiple_of(word))
+        .max()
+        .unwrap_or(word);
+    let cacheline = unsafe { ngx_cacheline_size };
 
-    #[cfg(ngx_feature = "pcre2")]
-    let rc: ngx::ffi::ngx_int_t = unsafe {
-        let mut key_ngx = key_ngx;
-        ngx_regex_exec(regex.regex, &raw mut key_ngx, core::ptr::null_mut(), 0)
+    let mut hash = ngx_hash_t {
+        buckets: core::ptr::null_mut(),
+        size: 0,
     };
+    let mut hinit = ngx_hash_init_t {
+        hash: &raw mut hash,
+        key: Some(ngx_hash_key),
+        max_size: (names.len() * 4).max(512),
+        bucket_size: (longest * 4 + word)
+            .next_multiple_of(cacheline)
+            .min(65536 - cacheline),
+        name: c"args_filter_literals".as_ptr().cast_mut(),
+        pool: unsafe { (*cf).pool },
+        temp_pool: unsafe { (*cf).temp_pool },
+    };
+
+    if unsafe { ngx_hash_init(&raw mut hinit, names.as_mut_ptr(), names.len()) } != NgxStatus::OK {
+        return Err(AllocError);
+    }
+    Ok(Some(hash))
+}
+
+/// Return true when `key` is one of the keys hashed by [`hash_literal_keys`].
+fn hash_contains(hash: &ngx_hash_t, key: &[u8]) -> bool {
+    let data = key.as_ptr().cast_mut();
+    unsafe {
+        let key_hash = ngx_hash_key(data, key.len());
+        !ngx_hash_find(
+            core::ptr::from_ref(hash).cast_mut(),
+            key_hash,
+            data,
+            key.len(),
+        )
+        .is_null()
+    }
+}
+
+/// Replace the combinable regexes of a rule run with one alternation of them,
+/// placed first. When the alternation does not compile, for example because
+/// two patterns declare the same group name, the regexes are left as they are.
+fn combine_regexes(cf: *mut ngx::ffi::ngx_conf_t, regexes: &mut Vec<CompiledRegex, Pool>) {
+    let combinable = regexes
+        .iter()
+        .filter(|regex| is_combinable_regex(regex.pattern.as_bytes()))
+        .count();
+    if combinable < REGEX_RUN_MIN {
+        return;
+    }
 
-    #[cfg(not(ngx_feature = "pcre2"))]
-    let rc: ngx::ffi::ngx_int_t = unsafe {
-        pcre_exec(
-            (*regex.regex).code,
-            (*regex.regex).extra,
-            key_ngx.data.cast(),
-            key_ngx.len as core::ffi::c_int,
-            0,
-            0,
-            core::ptr::null_mut(),
-            0,
-        ) as _
+    let mut pattern = std::vec::Vec::new();
+    for regex in regexes
+        .iter()
+        .filter(|regex| is_combinable_regex(regex.pattern.as_bytes()))
+    {
+        if !pattern.is_empty() {
+            pattern.push(b'|');
+        }
+        pattern.extend_from_slice(if regex.case_insensitive {
+            b"(?i:"
+        } else {
+            b"(?:"
+        });
+        pattern.extend_from_slice(regex.pattern.as_bytes());
+        pattern.push(b')');
+    }
+
+    // nginx keeps the pattern pointer for the JIT pass at startup, and PCRE1
+    // reads it as a C string, so it lives in the config pool with a final NUL.
+    let data = unsafe { ngx_pnalloc((*cf).pool, pattern.len() + 1) }.cast::<u8>();
+    if data.is_null() {
+        debug!("args_filter: regex rules left separate: failed to allocate pattern");
+        return;
+    }
+    unsafe {
+        core::ptr::copy_nonoverlapping(pattern.as_ptr(), data, pattern.len());
+        *data.add(pattern.len()) = 0;
+    }
+
+    let source = ngx_str_t {
+        len: pattern.len(),
+        data,
+    };
+    let combined = match try_compile_regex(cf, source, false) {
+        Ok(combined) => combined,
+        Err(err) => {
+            debug!("args_filter: regex rules left separate: {}", err);
+            return;
+        }
     };
 
-    if rc >= 0 {
-        return true;
+    regexes.retain(|regex| !is_combinable_regex(regex.pattern.as_bytes()));
+    regexes.insert(0, combined);
+}
+
+/// True when `pattern` means the same as the branch `(?:pattern)` of a larger
+/// alternation. Back-references, recursion, subroutine calls, and conditional
+/// groups address groups by number across the whole pattern, `(*VERB)`
+/// settings and `\Q` quoting can reach past the branch, and `x` mode comments
+/// run to the end of the line; patterns using any of them are left separate.
+fn is_combinable_regex(pattern: &[u8]) -> bool {
+    let mut idx = 0;
+
+    while let Some(&b) = pattern.get(idx) {
+        match (b, pattern.get(idx + 1..).unwrap_or_default()) {
+            (b'\\', [b'1'..=b'9' | b'g' | b'k' | b'Q', ..]) | (b'(', [b'*', ..]) => return false,
+            (b'\\', _) => idx += 2,
+            (b'(', [b'?', group @ ..]) => {
+                if !is_combinable_group(group) {
+                    return false;
+                }
+                idx += 2;
+            }
+            _ => idx += 1,
+        }
     }
 
-    if rc != NgxStatus::DECLINED {
-        error!("regex execution failed with rc={}", rc);
+    true
+}
+
+/// Check the text following `(?` of one group.
+fn is_combinable_group(group: &[u8]) -> bool {
+    match group {
+        [b'P', b'=' | b'>', ..]
+        | [b'(' | b'|' | b'R' | b'&' | b'+' | b'0'..=b'9', ..]
+        | [b'-', b'0'..=b'9', ..] => false,
+        _ => !group
+            .iter()
+            .take_while(|b| b.is_ascii_alphabetic() || matches!(b, b'-' | b'^'))
+            .any(|&b| b == b'x'),
     }
+}
 
-    false
+/// Return true when a literal rule operand references nginx variables.
+pub fn has_variables(value: &ngx_str_t) -> bool {
+    unsafe { ngx::ffi::ngx_http_script_variables_count(core::ptr::from_ref(value).cast_mut()) > 0 }
+}
+
+pub fn compile_complex_value(
+    cf: *mut ngx::ffi::ngx_conf_t,
+    value: ngx_str_t,
+) -> Result<CompiledComplexValue, ()> {
+    let cv = unsafe { ngx_pcalloc((*cf).pool, core::mem::size_of::<ngx_http_complex_value_t>()) }
+        .cast::<ngx_http_complex_value_t>();
+    if cv.is_null() {
+        error!("failed to allocate complex value");
+        return Err(());
+    }
+
+    let mut value = value;
+    let mut ccv = unsafe { core::mem::zeroed::<ngx_http_compile_complex_value_t>() };
+    ccv.cf = cf;
+    ccv.value = &raw mut value;
+    ccv.complex_value = cv;
+
+    if unsafe { ngx_http_compile_complex_value(&raw mut ccv) } != NgxStatus::OK {
+        error!("failed to compile variable rule operand");
+        return Err(());
+    }
+
+    Ok(CompiledComplexValue { value: cv })
+}
+
+pub fn evaluate_complex_value(req: &Request, cv: CompiledComplexValue) -> Option<&[u8]> {
+    let value = req.get_complex_value(unsafe { &*cv.value });
+    if value.is_none() {
+        error!("args_filter: failed to evaluate complex value");
+    }
+    value.map(NgxStr::as_bytes)
+}
+
+/// `if=` semantics shared with `access_log`: false for an empty value or "0".
+fn condition_holds(value: Option<&[u8]>) -> bool {
+    value.is_some_and(|v| !v.is_empty() && v != b"0")
+}
+
+/// Match `key` against a glob where `*` is any run of bytes and `?` is one byte.
+fn glob_matches(pattern: &[u8], key: &[u8]) -> bool {
+    let (mut p, mut k) = (0, 0);
+    // Position after the last `*` and the key position it currently absorbs up to.
+    let mut backtrack = None;
+
+    while k < key.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                p += 1;
+                backtrack = Some((p, k));
+            }
+            Some(&b) if b == b'?' || b == key[k] => {
+                p += 1;
+                k += 1;
+            }
+            _ => {
+                let Some((star_p, star_k)) = backtrack else {
+                    return false;
+                };
+                p = star_p;
+                k = star_k + 1;
+                backtrack = Some((star_p, k));
+            }
+        }
+    }
+
+    pattern[p..].iter().all(|&b| b == b'*')
+}
+
+fn regex_matches(
+    regex: &CompiledRegex,
+    subject: &[u8],
+    limits: &RegexLimits,
+) -> Result<bool, RegexLimitExceeded> {
+    match limits.exec(regex.regex, subject) {
+        RegexExec::Matched => Ok(true),
+        RegexExec::NotMatched => Ok(false),
+        RegexExec::LimitExceeded => Err(RegexLimitExceeded),
+        RegexExec::Failed(rc) => {
+            error!("regex execution failed with rc={}", rc);
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_matches, is_combinable_regex};
+
+    #[test]
+    fn glob_star_matches_any_run_of_bytes() {
+        assert!(glob_matches(b"ads.*.id", b"ads.x.id"));
+        assert!(glob_matches(b"ads.*.id", b"ads.a.b.id"));
+        assert!(glob_matches(b"ads.*.id", b"ads..id"));
+        assert!(!glob_matches(b"ads.*.id", b"ads.x.idx"));
+        assert!(glob_matches(b"*", b""));
+        assert!(glob_matches(b"a*b*c", b"aXbYbZc"));
+    }
+
+    #[test]
+    fn glob_question_mark_matches_one_byte() {
+        assert!(glob_matches(b"v?", b"v1"));
+        assert!(!glob_matches(b"v?", b"v"));
+        assert!(!glob_matches(b"v?", b"v12"));
+    }
+
+    #[test]
+    fn combinable_regexes_are_self_contained() {
+        assert!(is_combinable_regex(b"^utm_"));
+        assert!(is_combinable_regex(br"^(ad|gc)lid$"));
+        assert!(is_combinable_regex(br"(?i)token|(?<name>x)(?=y)"));
+        assert!(is_combinable_regex(br"\(\*\\1"));
+        assert!(!is_combinable_regex(br"^(a)\1$"));
+        assert!(!is_combinable_regex(br"(?<n>a)\k<n>"));
+        assert!(!is_combinable_regex(br"(a)(?1)"));
+        assert!(!is_combinable_regex(br"(?R)"));
+        assert!(!is_combinable_regex(br"^(a)?(?(1)b|c)$"));
+        assert!(!is_combinable_regex(br"(?<n>a)?(?(<n>)b|c)"));
+        assert!(!is_combinable_regex(br"(?(R)a|b)"));
+        assert!(!is_combinable_regex(br"\Qa|b"));
+        assert!(!is_combinable_regex(b"(*UCP)x"));
+        assert!(!is_combinable_regex(b"(?ix) a # comment"));
+    }
 }
@@ -0,0 +1,36 @@
+//! Arguments written into the filtered output by `set <key> <value>` and
+//! `add <key> <value>`.
+//!
+//! Values are complex values, evaluated for each request and percent-encoded
+//! when they are appended after the kept arguments.
+
+use crate::config::args_filter::CompiledComplexValue;
+use crate::nginx_str::NginxStr;
+use ngx::core::Pool;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InjectAction {
+    /// Replace every kept occurrence of the key with one new argument.
+    Set,
+    /// Append a new argument and leave existing occurrences alone.
+    Add,
+}
+
+impl InjectAction {
+    /// Name of the nested directive that declares arguments with this action.
+    pub const fn directive_name(self) -> &'static str {
+        match self {
+            Self::Set => "set",
+            Self::Add => "add",
+        }
+    }
+}
+
+/// One `set` or `add` directive.
+#[derive(Clone, Debug)]
+pub struct InjectedArg {
+    pub action: InjectAction,
+    /// Key written as given.
+    pub key: NginxStr<Pool>,
+    pub value: CompiledComplexValue,
+}
@@ -0,0 +1,184 @@
+//! Config-time checks for `args_filter` rules that can never affect the output
+//! or whose regexes risk catastrophic backtracking.
+//!
+//! Findings are reported as warnings, or as errors under `args_filter_strict on;`.
+//! Rules inherited through `extends` are only used as context: overriding a base
+//! rule is the point of `extends`, so it is never reported.
+
+use crate::config::args_filter::{
+    ArgsFilterDef, InitialPolicy, MatchMode, Rule, RuleAction, RuleMatcher,
+};
+use crate::config::regex_limits::{RegexLimits, has_nested_quantifier};
+
+impl ArgsFilterDef {
+    /// Return one message per rule problem found in this filter.
+    pub fn lint(&self) -> Vec<String> {
+        let rules = self
+            .rules
+            .as_ref()
+            .map_or(&[][..], ngx::collections::Vec::as_slice);
+        let catch_all = match self.match_mode {
+            MatchMode::Last => rules.iter().rposition(is_catch_all),
+            MatchMode::First => rules.iter().position(is_catch_all),
+        };
+        let mut findings = Vec::new();
+
+        for (idx, rule) in rules.iter().enumerate().skip(self.inherited_rules) {
+            if let Some(earlier) = duplicate_literal(rules, self.inherited_rules, idx) {
+                findings.push(format!(
+                    "rule[{idx}] {rule} repeats the literal of rule[{earlier}] {}",
+                    rules[earlier]
+                ));
+            }
+
+            if backtracks_catastrophically(rule) {
+                findings.push(format!(
+                    "rule[{idx}] {rule} repeats a group with an unbounded quantifier and may backtrack catastrophically"
+                ));
+            }
+
+            match (self.match_mode, catch_all) {
+                (MatchMode::Last, Some(c)) if idx < c => findings.push(format!(
+                    "rule[{idx}] {rule} is always overridden by catch-all rule[{c}] {}",
+                    rules[c]
+                )),
+                (MatchMode::First, Some(c)) if idx > c => findings.push(format!(
+                    "rule[{idx}] {rule} is never reached after catch-all rule[{c}] {}",
+                    rules[c]
+                )),
+                _ => {}
+            }
+
+            // Last match wins: an exclude undoes earlier includes. First match
+            // wins: an exclude shadows later includes.
+            let related = match self.match_mode {
+                MatchMode::Last => &rules[..idx],
+                MatchMode::First => &rules[idx + 1..],
+            };
+            if self.initial == InitialPolicy::None
+                && matches!(rule.action, RuleAction::Exclude)
+                && !can_exclude_included_key(related, rule)
+            {
+                findings.push(format!(
+                    r#"rule[{idx}] {rule} has no effect under "initial none": no {} rule includes a key it matches"#,
+                    match self.match_mode {
+                        MatchMode::Last => "earlier",
+                        MatchMode::First => "later",
+                    }
+                ));
+            }
+        }
+
+        if self.always_empty(rules, catch_all) {
+            findings.push("filter always produces an empty value".to_owned());
+        }
+
+        findings
+    }
+
+    fn always_empty(&self, rules: &[Rule], catch_all: Option<usize>) -> bool {
+        if self.injected_args.is_some() {
+            return false;
+        }
+
+        let Some(c) = catch_all else {
+            return self.initial == InitialPolicy::None && !has_include(rules);
+        };
+
+        let reachable = match self.match_mode {
+            MatchMode::Last => &rules[c + 1..],
+            MatchMode::First => &rules[..c],
+        };
+        matches!(rules[c].action, RuleAction::Exclude) && !has_include(reachable)
+    }
+}
+
+fn has_include(rules: &[Rule]) -> bool {
+    rules
+        .iter()
+        .any(|rule| matches!(rule.action, RuleAction::Include))
+}
+
+/// Index of an earlier unconditional rule, declared in this block, with the same literal.
+fn duplicate_literal(rules: &[Rule], inherited: usize, idx: usize) -> Option<usize> {
+    let rule = &rules[idx];
+    let RuleMatcher::Literal(key) = &rule.matcher else {
+        return None;
+    };
+
+    if rule.condition.is_some() || rule.value.is_some() {
+        return None;
+    }
+
+    (inherited..idx).find(|&earlier| {
+        let other = &rules[earlier];
+        other.condition.is_none()
+            && other.value.is_none()
+            && matches!(&other.matcher, RuleMatcher::Literal(other_key) if other_key == key)
+    })
+}
+
+/// Under `initial none`, an `exclude` only matters together with an `include`
+/// that can keep one of the keys it matches. Only literal includes can be checked
+/// exactly; any other include is assumed to overlap.
+fn can_exclude_included_key(related: &[Rule], exclude: &Rule) -> bool {
+    let mut includes = related
+        .iter()
+        .filter(|rule| matches!(rule.action, RuleAction::Include))
+        .peekable();
+
+    if includes.peek().is_none() {
+        return false;
+    }
+
+    if matches!(
+        exclude.matcher,
+        RuleMatcher::Complex(_) | RuleMatcher::Zone(_)
+    ) {
+        return true;
+    }
+
+    includes.any(|include| match &include.matcher {
+        RuleMatcher::Literal(key) => exclude
+            .matches_key(key.as_bytes(), None, &RegexLimits::new())
+            .unwrap_or(true),
+        RuleMatcher::CaselessLiteral(_)
+        | RuleMatcher::Prefix(_)
+        | RuleMatcher::Suffix(_)
+        | RuleMatcher::Glob(_)
+        | RuleMatcher::Path(_)
+        | RuleMatcher::Regex(_)
+        | RuleMatcher::Complex(_)
+        | RuleMatcher::Zone(_)
+        | RuleMatcher::Any => true,
+    })
+}
+
+/// True for an unconditional regex or glob rule whose pattern matches every key
+/// and that does not depend on the value.
+fn is_catch_all(rule: &Rule) -> bool {
+    if rule.condition.is_some() || rule.value.is_some() {
+        return false;
+    }
+
+    match &rule.matcher {
+        RuleMatcher::Regex(regex) => matches!(
+            regex.pattern.as_bytes(),
+            b"" | b"^" | b"$" | b".*" | b"^.*" | b".*$" | b"^.*$"
+        ),
+        RuleMatcher::Glob(pattern) => pattern.as_bytes().iter().all(|&b| b == b'*'),
+        _ => false,
+    }
+}
+
+/// True when the key or value regex of `rule` nests unbounded quantifiers.
+fn backtracks_catastrophically(rule: &Rule) -> bool {
+    let key = match &rule.matcher {
+        RuleMatcher::Regex(regex) => Some(regex),
+        _ => None,
+    };
+
+    key.into_iter()
+        .chain(rule.value.as_ref())
+        .any(|regex| has_nested_quantifier(regex.pattern.as_bytes()))
+}
@@ -0,0 +1,79 @@
+//! Location configuration structure.
+
+use crate::NgxArgsFilterModule;
+use crate::config::args_filter::{ArgsFilterDef, ArgsFilterMap};
+use ngx::core::Pool;
+use ngx::ffi::{NGX_HTTP_LOC_CONF, ngx_conf_t, ngx_uint_t};
+use ngx::http::{HttpModuleLocationConf, Merge, MergeConfigError};
+use std::fmt;
+use std::ptr::NonNull;
+
+/// Module location configuration.
+#[derive(Default)]
+pub struct LocConf {
+    /// `args_filter` definitions declared directly in this `location` block.
+    pub args_filters: Option<ArgsFilterMap>,
+    /// Configuration of the enclosing `location`, linked when this one is
+    /// created so that it can be followed while the configuration is parsed.
+    parent: Option<NonNull<LocConf>>,
+}
+
+impl LocConf {
+    /// Return the innermost definition of `name` visible from this location.
+    pub fn find_filter(&self, name: &[u8]) -> Option<&ArgsFilterDef> {
+        let mut scope = Some(self);
+
+        while let Some(conf) = scope {
+            if let Some(filter) = conf.args_filters.as_ref().and_then(|m| m.get(name)) {
+                return Some(filter);
+            }
+
+            // SAFETY: parent configurations are allocated from the cycle pool and
+            // outlive every location that inherits from them.
+            scope = conf.parent.map(|p| unsafe { p.as_ref() });
+        }
+
+        None
+    }
+}
+
+impl Merge for LocConf {
+    fn merge(&mut self, _prev: &Self) -> Result<(), MergeConfigError> {
+        // Definitions are inherited through `parent`, which is set on creation.
+        Ok(())
+    }
+}
+
+/// # Safety
+///
+/// Caller must pass valid NGINX pointers.
+pub unsafe extern "C" fn create_loc_config(cf: *mut ngx_conf_t) -> *mut core::ffi::c_void {
+    let cf_ref = unsafe { &*cf };
+
+    // nginx creates a nested location's configuration while the enclosing
+    // location is still the current context.
+    let parent = if cf_ref.cmd_type == NGX_HTTP_LOC_CONF as ngx_uint_t {
+        NgxArgsFilterModule::location_conf_mut(cf_ref).map(NonNull::from)
+    } else {
+        None
+    };
+
+    let pool = unsafe { Pool::from_ngx_pool(cf_ref.pool) };
+    pool.allocate(LocConf {
+        args_filters: None,
+        parent,
+    })
+    .cast()
+}
+
+impl fmt::Debug for LocConf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocConf")
+            .field(
+                "args_filters_count",
+                &self.args_filters.as_ref().map(|m| m.iter().count()),
+            )
+            .field("has_parent", &self.parent.is_some())
+            .finish()
+    }
+}
@@ -1,25 +1,105 @@
 //! Main configuration structure.
 
-use crate::config::args_filter::ArgsFilterDef;
+use crate::config::args_filter::{ArgsFilterMap, KeyListMap};
+use crate::config::zone::KeyZone;
 use crate::nginx_str::NginxStr;
+use ngx::collections::Vec;
 use ngx::collections::rbtree::RbTreeMap;
 use ngx::core::Pool;
+use ngx::ffi::{ngx_http_core_loc_conf_t, ngx_http_core_srv_conf_t, ngx_http_server_name_t};
 use std::fmt;
+use std::ptr::NonNull;
+
+/// Block that declares a `server` or `location` filter map.
+#[derive(Clone, Copy, Debug)]
+pub enum FilterScope {
+    Server(NonNull<ngx_http_core_srv_conf_t>),
+    Location(NonNull<ngx_http_core_loc_conf_t>),
+}
+
+impl fmt::Display for FilterScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // SAFETY: core configurations are allocated from the cycle pool and
+        // outlive the module configuration that points at them.
+        match *self {
+            Self::Server(cscf) => {
+                let names = unsafe { &cscf.as_ref().server_names };
+                if names.nelts == 0 {
+                    return write!(f, "server");
+                }
+                let name = unsafe { &(*names.elts.cast::<ngx_http_server_name_t>()).name };
+                write!(f, "server {}", String::from_utf8_lossy(name.as_bytes()))
+            }
+            Self::Location(clcf) => {
+                let name = unsafe { &clcf.as_ref().name };
+                write!(f, "location {}", String::from_utf8_lossy(name.as_bytes()))
+            }
+        }
+    }
+}
+
+/// A `server` or `location` filter map, recorded when the map is created.
+#[derive(Debug)]
+pub struct ScopedFilters {
+    pub scope: FilterScope,
+    /// Map owned by the block's `SrvConf` or `LocConf`.
+    pub filters: NonNull<ArgsFilterMap>,
+}
 
 /// Module main configuration.
 #[derive(Default)]
 pub struct MainConf {
-    /// Map of `args_filter` variable names to compiled definitions.
-    pub args_filters: Option<RbTreeMap<NginxStr<Pool>, ArgsFilterDef, Pool>>,
+    /// Map of `http`-level `args_filter` variable names to compiled definitions.
+    pub args_filters: Option<ArgsFilterMap>,
+    /// `server` and `location` filter maps in configuration order, for `args_filter_dump`.
+    pub scoped_filters: Option<Vec<ScopedFilters, Pool>>,
+    /// Named key lists declared with `args_filter_keys`.
+    pub key_lists: Option<KeyListMap>,
+    /// Shared memory key sets declared with `args_filter_zone`.
+    pub zones: Option<RbTreeMap<NginxStr<Pool>, KeyZone, Pool>>,
+    /// Report lint findings as errors instead of warnings (`args_filter_strict`).
+    pub strict: bool,
+    pub strict_set: bool,
+    /// Names of variables already registered with nginx from any context.
+    pub variables: Option<RbTreeMap<NginxStr<Pool>, (), Pool>>,
+}
+
+impl MainConf {
+    pub fn add_scoped_filters(&mut self, pool: Pool, scoped: ScopedFilters) {
+        if self.scoped_filters.is_none() {
+            self.scoped_filters = Some(Vec::new_in(pool));
+        }
+
+        if let Some(scoped_filters) = self.scoped_filters.as_mut() {
+            scoped_filters.push(scoped);
+        }
+    }
 }
 
 impl fmt::Debug for MainConf {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("MainConf")
+            .field("strict", &self.strict)
             .field(
                 "args_filters_count",
                 &self.args_filters.as_ref().map(|m| m.iter().count()),
             )
+            .field(
+                "scoped_filters_count",
+                &self.scoped_filters.as_ref().map(Vec::len),
+            )
+            .field(
+                "key_lists_count",
+                &self.key_lists.as_ref().map(|m| m.iter().count()),
+            )
+            .field(
+                "zones_count",
+                &self.zones.as_ref().map(|m| m.iter().count()),
+            )
+            .field(
+                "variables_count",
+                &self.variables.as_ref().map(|m| m.iter().count()),
+            )
             .finish()
     }
 }
@@ -2,6 +2,19 @@
 
 pub mod args_filter;
 pub mod init;
+pub mod inject;
+pub mod lint;
+pub mod loc;
 pub mod main;
+pub mod path;
+pub mod regex_limits;
+pub mod rename;
+pub mod rules_file;
+pub mod srv;
+pub mod validate;
+pub mod value_length;
+pub mod zone;
 
+pub use loc::LocConf;
 pub use main::MainConf;
+pub use srv::SrvConf;
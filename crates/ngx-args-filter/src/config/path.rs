@@ -0,0 +1,176 @@
+//! Component-wise matching of nested parameter names for `path:` rules.
+//!
+//! A key such as `filter[user][name]` or `a.b.c` is split into components and
+//! compared with the pattern one component at a time; a `*` component in the
+//! pattern matches exactly one key component.
+
+use crate::nginx_str::NginxStr;
+use ngx::core::Pool;
+
+/// Component separators recognized by `path:` matchers (`path_separator`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathSeparator {
+    /// `a[b][c]`
+    Brackets,
+    /// `a.b.c`
+    Dot,
+    /// Both forms, also mixed as in `a.b[c]`.
+    #[default]
+    Both,
+}
+
+impl PathSeparator {
+    /// Name as written in the `path_separator` directive.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Brackets => "brackets",
+            Self::Dot => "dot",
+            Self::Both => "both",
+        }
+    }
+
+    const fn brackets(self) -> bool {
+        matches!(self, Self::Brackets | Self::Both)
+    }
+
+    const fn dot(self) -> bool {
+        matches!(self, Self::Dot | Self::Both)
+    }
+}
+
+/// Compiled `path:` operand.
+#[derive(Clone, Debug)]
+pub struct KeyPath {
+    pub pattern: NginxStr<Pool>,
+    pub separator: PathSeparator,
+}
+
+impl KeyPath {
+    pub fn matches(&self, key: &[u8]) -> bool {
+        path_matches(self.pattern.as_bytes(), key, self.separator)
+    }
+}
+
+/// Compare `key` with `pattern` component by component.
+fn path_matches(pattern: &[u8], key: &[u8], separator: PathSeparator) -> bool {
+    let mut pattern = PathComponents::new(pattern, separator);
+    let mut key = PathComponents::new(key, separator);
+
+    loop {
+        match (pattern.next(), key.next()) {
+            (None, None) => return true,
+            (Some(expected), Some(component)) if expected == b"*" || expected == component => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Iterator over the components of a nested parameter name.
+///
+/// Malformed trailing text, such as `c` in `a[b]c` or an unclosed `[b`, is
+/// returned unchanged as one last component.
+struct PathComponents<'a> {
+    rest: &'a [u8],
+    separator: PathSeparator,
+    started: bool,
+}
+
+impl<'a> PathComponents<'a> {
+    const fn new(key: &'a [u8], separator: PathSeparator) -> Self {
+        Self {
+            rest: key,
+            separator,
+            started: false,
+        }
+    }
+
+    /// Split off the text up to the next separator.
+    fn take_name(&mut self) -> &'a [u8] {
+        let end = self
+            .rest
+            .iter()
+            .position(|&b| {
+                (b == b'[' && self.separator.brackets()) || (b == b'.' && self.separator.dot())
+            })
+            .unwrap_or(self.rest.len());
+        let (name, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        name
+    }
+}
+
+impl<'a> Iterator for PathComponents<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            return Some(self.take_name());
+        }
+
+        match self.rest.first()? {
+            b'.' if self.separator.dot() => {
+                self.rest = &self.rest[1..];
+                Some(self.take_name())
+            }
+            b'[' if self.separator.brackets() => {
+                let Some(close) = self.rest.iter().position(|&b| b == b']') else {
+                    return Some(core::mem::take(&mut self.rest));
+                };
+                let component = &self.rest[1..close];
+                self.rest = &self.rest[close + 1..];
+                Some(component)
+            }
+            _ => Some(core::mem::take(&mut self.rest)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PathComponents, PathSeparator, path_matches};
+
+    fn components(key: &[u8], separator: PathSeparator) -> Vec<&[u8]> {
+        PathComponents::new(key, separator).collect()
+    }
+
+    #[test]
+    fn splits_bracket_and_dot_paths() {
+        let both = PathSeparator::Both;
+        assert_eq!(
+            components(b"filter[user][name]", both),
+            [&b"filter"[..], b"user", b"name"]
+        );
+        assert_eq!(components(b"a.b[c]", both), [&b"a"[..], b"b", b"c"]);
+        assert_eq!(components(b"ids[]", both), [&b"ids"[..], b""]);
+        assert_eq!(components(b"a[b]c", both), [&b"a"[..], b"b", b"c"]);
+        assert_eq!(components(b"a[b", both), [&b"a"[..], b"[b"]);
+    }
+
+    #[test]
+    fn single_separator_treats_the_other_as_text() {
+        assert_eq!(
+            components(b"a.b[c]", PathSeparator::Brackets),
+            [&b"a.b"[..], b"c"]
+        );
+        assert_eq!(
+            components(b"a.b[c]", PathSeparator::Dot),
+            [&b"a"[..], b"b[c]"]
+        );
+    }
+
+    #[test]
+    fn star_matches_exactly_one_component() {
+        let both = PathSeparator::Both;
+        assert!(path_matches(
+            b"filter[*][name]",
+            b"filter[user][name]",
+            both
+        ));
+        assert!(path_matches(b"filter.*.name", b"filter[user][name]", both));
+        assert!(path_matches(b"*.secret", b"api.secret", both));
+        assert!(!path_matches(b"*.secret", b"a.b.secret", both));
+        assert!(!path_matches(b"*.secret", b"secret", both));
+        assert!(!path_matches(b"filter[*]", b"filter[a][b]", both));
+    }
+}
@@ -0,0 +1,448 @@
+//! Resource limits for rule regexes run against query arguments.
+//!
+//! Keys and values come straight from the client, so `regex_match_limit` and
+//! `regex_depth_limit` cap the work PCRE may spend on one of them, and
+//! `regex_limit_exceeded` decides what happens to an argument whose evaluation
+//! stopped at a limit. [`has_nested_quantifier`] backs the config-time warning
+//! about patterns that can backtrack catastrophically.
+
+use core::ffi::c_int;
+use ngx::allocator::AllocError;
+use ngx::core::Pool;
+use ngx::ffi::{ngx_int_t, ngx_regex_t};
+
+#[cfg(not(ngx_feature = "pcre2"))]
+use ngx::ffi::{
+    PCRE_ERROR_JIT_STACKLIMIT, PCRE_ERROR_MATCHLIMIT, PCRE_ERROR_NOMATCH,
+    PCRE_ERROR_RECURSIONLIMIT, PCRE_EXTRA_MATCH_LIMIT, PCRE_EXTRA_MATCH_LIMIT_RECURSION, pcre_exec,
+    pcre_extra,
+};
+#[cfg(ngx_feature = "pcre2")]
+use ngx::ffi::{
+    PCRE2_ERROR_DEPTHLIMIT, PCRE2_ERROR_HEAPLIMIT, PCRE2_ERROR_JIT_STACKLIMIT,
+    PCRE2_ERROR_MATCHLIMIT, PCRE2_ERROR_NOMATCH, ngx_regex_exec, ngx_str_t,
+    pcre2_get_ovector_count_8, pcre2_get_ovector_pointer_8, pcre2_match_8, pcre2_match_context_8,
+    pcre2_match_context_create_8, pcre2_match_context_free_8, pcre2_match_data_8,
+    pcre2_match_data_create_8, pcre2_match_data_free_8, pcre2_set_depth_limit_8,
+    pcre2_set_match_limit_8,
+};
+
+/// Capture pairs filled by [`RegexLimits::exec_captures`]: the whole match
+/// and groups 1 to 9.
+pub const CAPTURE_PAIRS: usize = 10;
+
+/// Capture offsets as `ngx_regex_exec` fills them: start and end of each
+/// pair, `-1` for unset groups, and a last third used as PCRE1 workspace.
+pub type Captures = [c_int; CAPTURE_PAIRS * 3];
+
+/// What happens to an argument when a rule regex stops at a PCRE limit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum RegexLimitPolicy {
+    /// Keep the argument without evaluating further rules.
+    Keep,
+    /// Drop the argument without evaluating further rules.
+    #[default]
+    Drop,
+    /// Fail the whole variable, which is then not found.
+    Fail,
+}
+
+impl RegexLimitPolicy {
+    /// Name as written in the `regex_limit_exceeded` directive.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Keep => "keep",
+            Self::Drop => "drop",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+/// A rule regex stopped at a match, depth, heap, or JIT stack limit.
+#[derive(Clone, Copy, Debug)]
+pub struct RegexLimitExceeded;
+
+/// Outcome of one regex execution.
+pub enum RegexExec {
+    Matched,
+    NotMatched,
+    LimitExceeded,
+    /// Any other PCRE error code.
+    Failed(ngx_int_t),
+}
+
+/// PCRE limits of one filter; zero keeps the library default.
+#[derive(Debug)]
+pub struct RegexLimits {
+    pub match_limit: u32,
+    pub depth_limit: u32,
+    pub exceeded: RegexLimitPolicy,
+    /// Match context carrying the limits; built by [`Self::prepare`].
+    #[cfg(ngx_feature = "pcre2")]
+    matcher: *mut Pcre2Matcher,
+}
+
+impl Default for RegexLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RegexLimits {
+    pub const fn new() -> Self {
+        Self {
+            match_limit: 0,
+            depth_limit: 0,
+            exceeded: RegexLimitPolicy::Drop,
+            #[cfg(ngx_feature = "pcre2")]
+            matcher: core::ptr::null_mut(),
+        }
+    }
+
+    /// Copy the configured settings of `base`, but not its match context.
+    pub const fn copy_settings(&mut self, base: &Self) {
+        self.match_limit = base.match_limit;
+        self.depth_limit = base.depth_limit;
+        self.exceeded = base.exceeded;
+    }
+
+    const fn is_set(&self) -> bool {
+        self.match_limit > 0 || self.depth_limit > 0
+    }
+
+    /// Create the PCRE2 match context for the configured limits; the context
+    /// is freed with `pool`.
+    #[cfg_attr(
+        not(ngx_feature = "pcre2"),
+        allow(clippy::unnecessary_wraps, clippy::unused_self)
+    )]
+    pub fn prepare(&mut self, pool: &Pool) -> Result<(), AllocError> {
+        #[cfg(ngx_feature = "pcre2")]
+        if self.is_set() {
+            let matcher = pool.allocate(Pcre2Matcher {
+                context: unsafe { pcre2_match_context_create_8(core::ptr::null_mut()) },
+                data: unsafe {
+                    pcre2_match_data_create_8(
+                        u32::try_from(CAPTURE_PAIRS).unwrap_or(1),
+                        core::ptr::null_mut(),
+                    )
+                },
+            });
+            let Some(m) = (unsafe { matcher.as_ref() }) else {
+                return Err(AllocError);
+            };
+            if m.context.is_null() || m.data.is_null() {
+                return Err(AllocError);
+            }
+
+            unsafe {
+                if self.match_limit > 0 {
+                    pcre2_set_match_limit_8(m.context, self.match_limit);
+                }
+                if self.depth_limit > 0 {
+                    pcre2_set_depth_limit_8(m.context, self.depth_limit);
+                }
+            }
+            self.matcher = matcher;
+        }
+
+        #[cfg(not(ngx_feature = "pcre2"))]
+        let _ = pool;
+
+        Ok(())
+    }
+
+    /// Run `regex` against `subject` under these limits.
+    pub fn exec(&self, regex: *mut ngx_regex_t, subject: &[u8]) -> RegexExec {
+        Self::classify(self.exec_raw(regex, subject, &mut []))
+    }
+
+    /// Like [`Self::exec`], also storing the offsets of the whole match and
+    /// groups 1 to 9 in `captures`.
+    pub fn exec_captures(
+        &self,
+        regex: *mut ngx_regex_t,
+        subject: &[u8],
+        captures: &mut Captures,
+    ) -> RegexExec {
+        captures.fill(-1);
+        Self::classify(self.exec_raw(regex, subject, captures))
+    }
+
+    fn classify(rc: ngx_int_t) -> RegexExec {
+        if rc >= 0 {
+            return RegexExec::Matched;
+        }
+
+        #[cfg(ngx_feature = "pcre2")]
+        let (no_match, limits) = (
+            PCRE2_ERROR_NOMATCH,
+            [
+                PCRE2_ERROR_MATCHLIMIT,
+                PCRE2_ERROR_DEPTHLIMIT,
+                PCRE2_ERROR_HEAPLIMIT,
+                PCRE2_ERROR_JIT_STACKLIMIT,
+            ],
+        );
+        #[cfg(not(ngx_feature = "pcre2"))]
+        let (no_match, limits) = (
+            PCRE_ERROR_NOMATCH,
+            [
+                PCRE_ERROR_MATCHLIMIT,
+                PCRE_ERROR_RECURSIONLIMIT,
+                PCRE_ERROR_JIT_STACKLIMIT,
+            ],
+        );
+
+        if rc == no_match as ngx_int_t {
+            RegexExec::NotMatched
+        } else if limits.iter().any(|&limit| rc == limit as ngx_int_t) {
+            RegexExec::LimitExceeded
+        } else {
+            RegexExec::Failed(rc)
+        }
+    }
+
+    #[cfg(ngx_feature = "pcre2")]
+    fn exec_raw(
+        &self,
+        regex: *mut ngx_regex_t,
+        subject: &[u8],
+        captures: &mut [c_int],
+    ) -> ngx_int_t {
+        if let Some(matcher) = unsafe { self.matcher.as_ref() } {
+            let rc = unsafe {
+                pcre2_match_8(
+                    regex,
+                    subject.as_ptr(),
+                    subject.len(),
+                    0,
+                    0,
+                    matcher.data,
+                    matcher.context,
+                )
+            };
+
+            if rc >= 0 {
+                let pairs = unsafe { pcre2_get_ovector_count_8(matcher.data) } as usize;
+                let ovector = unsafe {
+                    std::slice::from_raw_parts(pcre2_get_ovector_pointer_8(matcher.data), pairs * 2)
+                };
+                let len = (captures.len() / 3 * 2).min(ovector.len());
+                for (capture, offset) in captures[..len].iter_mut().zip(ovector) {
+                    *capture = c_int::try_from(*offset).unwrap_or(-1);
+                }
+            }
+            return rc as ngx_int_t;
+        }
+
+        let mut subject = ngx_str_t {
+            len: subject.len(),
+            data: subject.as_ptr().cast_mut(),
+        };
+        unsafe {
+            ngx_regex_exec(
+                regex,
+                &raw mut subject,
+                captures.as_mut_ptr(),
+                captures.len() as _,
+            )
+        }
+    }
+
+    #[cfg(not(ngx_feature = "pcre2"))]
+    fn exec_raw(
+        &self,
+        regex: *mut ngx_regex_t,
+        subject: &[u8],
+        captures: &mut [c_int],
+    ) -> ngx_int_t {
+        let regex = unsafe { &*regex };
+        let mut extra = regex.extra;
+        let mut limited: pcre_extra = if regex.extra.is_null() {
+            unsafe { core::mem::zeroed() }
+        } else {
+            unsafe { *regex.extra }
+        };
+
+        if self.is_set() {
+            if self.match_limit > 0 {
+                limited.flags |= core::ffi::c_ulong::from(PCRE_EXTRA_MATCH_LIMIT);
+                limited.match_limit = core::ffi::c_ulong::from(self.match_limit);
+            }
+            if self.depth_limit > 0 {
+                limited.flags |= core::ffi::c_ulong::from(PCRE_EXTRA_MATCH_LIMIT_RECURSION);
+                limited.match_limit_recursion = core::ffi::c_ulong::from(self.depth_limit);
+            }
+            extra = &raw mut limited;
+        }
+
+        unsafe {
+            pcre_exec(
+                regex.code,
+                extra,
+                subject.as_ptr().cast(),
+                subject.len() as core::ffi::c_int,
+                0,
+                0,
+                captures.as_mut_ptr(),
+                c_int::try_from(captures.len()).unwrap_or(0),
+            ) as ngx_int_t
+        }
+    }
+}
+
+/// PCRE2 match context and match data owned by one filter.
+#[cfg(ngx_feature = "pcre2")]
+#[derive(Debug)]
+struct Pcre2Matcher {
+    context: *mut pcre2_match_context_8,
+    data: *mut pcre2_match_data_8,
+}
+
+#[cfg(ngx_feature = "pcre2")]
+impl Drop for Pcre2Matcher {
+    fn drop(&mut self) {
+        unsafe {
+            pcre2_match_context_free_8(self.context);
+            pcre2_match_data_free_8(self.data);
+        }
+    }
+}
+
+/// Return true when `pattern` repeats a group that itself contains an
+/// unbounded quantifier, as in `(a+)+` or `(\w+\s?)*`. Such a group can split
+/// one input in exponentially many ways before a match fails.
+///
+/// Possessive quantifiers and atomic groups never backtrack and are ignored.
+pub fn has_nested_quantifier(pattern: &[u8]) -> bool {
+    // One entry per open group: (contains an unbounded quantifier, atomic).
+    let mut groups: Vec<(bool, bool)> = Vec::new();
+    let mut idx = 0;
+
+    while let Some(&b) = pattern.get(idx) {
+        idx += 1;
+        match b {
+            b'\\' => idx += 1,
+            b'[' => idx = class_end(pattern, idx),
+            b'(' => {
+                if pattern.get(idx) == Some(&b'*') {
+                    // `(*VERB)` is not a group.
+                    idx = pattern[idx..]
+                        .iter()
+                        .position(|&c| c == b')')
+                        .map_or(pattern.len(), |end| idx + end + 1);
+                } else {
+                    groups.push((false, pattern[idx..].starts_with(b"?>")));
+                    idx += usize::from(pattern.get(idx) == Some(&b'?'));
+                }
+                continue;
+            }
+            b')' => {
+                let Some((nested, atomic)) = groups.pop() else {
+                    continue;
+                };
+                let nested = nested && !atomic;
+                let (unbounded, len) = quantifier(&pattern[idx..]);
+                if nested && unbounded {
+                    return true;
+                }
+                idx += len;
+                if (nested || unbounded)
+                    && let Some(outer) = groups.last_mut()
+                {
+                    outer.0 = true;
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let (unbounded, len) = quantifier(&pattern[idx..]);
+        idx += len;
+        if unbounded && let Some(group) = groups.last_mut() {
+            group.0 = true;
+        }
+    }
+
+    false
+}
+
+/// Index just past the `]` closing a character class opened before `idx`.
+fn class_end(pattern: &[u8], mut idx: usize) -> usize {
+    if pattern.get(idx) == Some(&b'^') {
+        idx += 1;
+    }
+    // A `]` right after `[` or `[^` is a literal.
+    if pattern.get(idx) == Some(&b']') {
+        idx += 1;
+    }
+
+    while let Some(&b) = pattern.get(idx) {
+        idx += 1;
+        match b {
+            b'\\' => idx += 1,
+            b']' => return idx,
+            _ => {}
+        }
+    }
+
+    pattern.len()
+}
+
+/// Parse a quantifier at the start of `rest`: whether it is unbounded and
+/// backtracks, and its length.
+fn quantifier(rest: &[u8]) -> (bool, usize) {
+    let (unbounded, len) = match rest {
+        [b'*' | b'+', ..] => (true, 1),
+        [b'?', ..] => (false, 1),
+        [b'{', tail @ ..] => {
+            let Some(end) = tail.iter().position(|&c| c == b'}') else {
+                return (false, 0);
+            };
+            let body = &tail[..end];
+            let valid = !body.is_empty()
+                && body.iter().all(|&c| c.is_ascii_digit() || c == b',')
+                && body.iter().filter(|&&c| c == b',').count() <= 1
+                && body[0] != b',';
+            if !valid {
+                return (false, 0);
+            }
+            (body.ends_with(b","), end + 2)
+        }
+        _ => return (false, 0),
+    };
+
+    match rest.get(len) {
+        // Possessive: no backtracking into the repetition.
+        Some(b'+') => (false, len + 1),
+        Some(b'?') => (unbounded, len + 1),
+        _ => (unbounded, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::has_nested_quantifier;
+
+    #[test]
+    fn flags_repeated_groups_with_unbounded_quantifiers() {
+        assert!(has_nested_quantifier(b"^(a+)+$"));
+        assert!(has_nested_quantifier(br"(\w+\s?)*x"));
+        assert!(has_nested_quantifier(b"(?:[a-z]*,){2,}"));
+        assert!(has_nested_quantifier(b"((ab)*c)+"));
+        assert!(has_nested_quantifier(b"(?i:x+)*?"));
+    }
+
+    #[test]
+    fn accepts_bounded_possessive_and_atomic_repetition() {
+        assert!(!has_nested_quantifier(b"^utm_[a-z]+$"));
+        assert!(!has_nested_quantifier(b"(a+)?"));
+        assert!(!has_nested_quantifier(b"(a+){1,3}"));
+        assert!(!has_nested_quantifier(b"(a++)+"));
+        assert!(!has_nested_quantifier(b"(?>a+)+"));
+        assert!(!has_nested_quantifier(br"\(a+\)+"));
+        assert!(!has_nested_quantifier(b"[(a+)+]"));
+        assert!(!has_nested_quantifier(b"(*UCP)(ab)+"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Key renaming for `rename <from> <to>` and `rename ~ <regex> <replacement>`.
+//!
+//! A rename only touches segments that are kept: rules, length limits, and
+//! validations all see the original key, and the first matching rename gives
+//! the segment its new name.
+
+use crate::config::args_filter::CompiledRegex;
+use crate::config::regex_limits::{
+    CAPTURE_PAIRS, Captures, RegexExec, RegexLimitExceeded, RegexLimits,
+};
+use crate::nginx_str::NginxStr;
+use ngx::core::Pool;
+use tracing::error;
+
+/// What a `rename` directive matches.
+#[derive(Clone, Debug)]
+pub enum RenameSource {
+    /// Normalized key, compared exactly.
+    Literal(NginxStr<Pool>),
+    /// Regex run against the normalized key; its captures feed the replacement.
+    Regex(CompiledRegex),
+}
+
+/// One `rename` directive.
+#[derive(Clone, Debug)]
+pub struct Rename {
+    pub source: RenameSource,
+    /// New key, written as is; for a regex source `$0` to `$9` are replaced
+    /// with the captured text.
+    pub to: NginxStr<Pool>,
+}
+
+impl Rename {
+    /// Append the new name of `key` to `out` when this rename matches it.
+    /// Captured text is appended through `push_capture`, which may escape it.
+    pub fn apply(
+        &self,
+        key: &[u8],
+        limits: &RegexLimits,
+        out: &mut std::vec::Vec<u8>,
+        push_capture: impl Fn(&mut std::vec::Vec<u8>, &[u8]),
+    ) -> Result<bool, RegexLimitExceeded> {
+        let regex = match &self.source {
+            RenameSource::Literal(from) => {
+                if from.as_bytes() != key {
+                    return Ok(false);
+                }
+                out.extend_from_slice(self.to.as_bytes());
+                return Ok(true);
+            }
+            RenameSource::Regex(regex) => regex,
+        };
+
+        let mut captures: Captures = [-1; CAPTURE_PAIRS * 3];
+        match limits.exec_captures(regex.regex, key, &mut captures) {
+            RegexExec::Matched => {}
+            RegexExec::NotMatched => return Ok(false),
+            RegexExec::LimitExceeded => return Err(RegexLimitExceeded),
+            RegexExec::Failed(rc) => {
+                error!("regex execution failed with rc={}", rc);
+                return Ok(false);
+            }
+        }
+
+        let to = self.to.as_bytes();
+        let mut idx = 0;
+        while idx < to.len() {
+            if let Some(group) = capture_reference(to, idx) {
+                if let Some(text) = captured(&captures, key, group) {
+                    push_capture(out, text);
+                }
+                idx += 2;
+            } else {
+                out.push(to[idx]);
+                idx += 1;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Check that every `$` in a regex `rename` replacement starts a `$0` to `$9`
+/// reference to a group the regex has.
+pub fn check_replacement(to: &[u8], captures: usize) -> Result<(), ()> {
+    for idx in (0..to.len()).filter(|&idx| to[idx] == b'$') {
+        match capture_reference(to, idx) {
+            None => {
+                error!(r#""rename" replacement may only use "$" in "$0" to "$9""#);
+                return Err(());
+            }
+            Some(group) if group > captures => {
+                error!(
+                    r#""rename" replacement refers to missing capture "${}""#,
+                    group
+                );
+                return Err(());
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Group number of a `$N` reference starting at `idx`.
+fn capture_reference(to: &[u8], idx: usize) -> Option<usize> {
+    match to.get(idx..idx + 2)? {
+        [b'$', digit @ b'0'..=b'9'] => Some(usize::from(digit - b'0')),
+        _ => None,
+    }
+}
+
+/// Text of capture `group` within `subject`; `None` when the group is unset.
+fn captured<'a>(captures: &Captures, subject: &'a [u8], group: usize) -> Option<&'a [u8]> {
+    let start = usize::try_from(captures[group * 2]).ok()?;
+    let end = usize::try_from(captures[group * 2 + 1]).ok()?;
+    subject.get(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{capture_reference, captured, check_replacement};
+    use crate::config::regex_limits::{CAPTURE_PAIRS, Captures};
+
+    #[test]
+    fn replacement_references_existing_groups() {
+        assert_eq!(capture_reference(b"x$1", 1), Some(1));
+        assert_eq!(capture_reference(b"$", 0), None);
+        assert_eq!(capture_reference(b"$x", 0), None);
+        assert!(check_replacement(b"aws_$1_$0", 1).is_ok());
+        assert!(check_replacement(b"plain", 0).is_ok());
+        assert!(check_replacement(b"$2", 1).is_err());
+        assert!(check_replacement(b"$name", 3).is_err());
+        assert!(check_replacement(b"a$", 3).is_err());
+    }
+
+    #[test]
+    fn unset_groups_capture_nothing() {
+        let mut captures: Captures = [-1; CAPTURE_PAIRS * 3];
+        captures[..4].copy_from_slice(&[0, 7, 4, 7]);
+        assert_eq!(captured(&captures, b"aws.key", 0), Some(&b"aws.key"[..]));
+        assert_eq!(captured(&captures, b"aws.key", 1), Some(&b"key"[..]));
+        assert_eq!(captured(&captures, b"aws.key", 2), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! Line syntax of `rules_file`: one rule per line, without a terminating `;`.
+//!
+//! Words are separated by spaces or tabs and may be quoted with `"` or `'`.
+//! As in the nginx configuration, `\"`, `\'`, `\\`, `\t`, `\r`, and `\n` are
+//! unescaped and any other `\` is kept, so regexes are written as in the block.
+
+/// Split one line of a rules file into words. A `#` at the start of a word
+/// starts a comment, so a blank or comment-only line has no words.
+pub fn split_rule_line(line: &[u8]) -> Result<Vec<Vec<u8>>, &'static str> {
+    let mut words = Vec::new();
+    let mut rest = line;
+
+    loop {
+        rest = trim_start(rest);
+
+        let (word, after) = match rest.first() {
+            None | Some(b'#') => return Ok(words),
+            Some(&quote @ (b'"' | b'\'')) => {
+                let (word, after) = quoted_word(&rest[1..], quote)?;
+                if after.first().is_some_and(|&b| !is_space(b)) {
+                    return Err("unexpected character after closing quote");
+                }
+                (word, after)
+            }
+            Some(_) => plain_word(rest)?,
+        };
+
+        words.push(word);
+        rest = after;
+    }
+}
+
+const fn is_space(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\r')
+}
+
+fn trim_start(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|&b| !is_space(b))
+        .unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// Append the byte that `\` followed by `escaped` stands for.
+fn push_escaped(word: &mut Vec<u8>, escaped: u8) {
+    match escaped {
+        b'"' | b'\'' | b'\\' => word.push(escaped),
+        b't' => word.push(b'\t'),
+        b'r' => word.push(b'\r'),
+        b'n' => word.push(b'\n'),
+        _ => word.extend_from_slice(&[b'\\', escaped]),
+    }
+}
+
+/// Read an unquoted word up to the next space.
+fn plain_word(bytes: &[u8]) -> Result<(Vec<u8>, &[u8]), &'static str> {
+    let mut word = Vec::new();
+    let mut i = 0;
+
+    while let Some(&b) = bytes.get(i) {
+        match b {
+            b'\\' if i + 1 < bytes.len() => {
+                push_escaped(&mut word, bytes[i + 1]);
+                i += 2;
+                continue;
+            }
+            b';' => return Err(r#"unexpected ";", rules in a rules file are not terminated"#),
+            _ if is_space(b) => break,
+            _ => word.push(b),
+        }
+        i += 1;
+    }
+
+    Ok((word, &bytes[i..]))
+}
+
+/// Read a word after its opening `quote` up to the closing one.
+fn quoted_word(bytes: &[u8], quote: u8) -> Result<(Vec<u8>, &[u8]), &'static str> {
+    let mut word = Vec::new();
+    let mut i = 0;
+
+    while let Some(&b) = bytes.get(i) {
+        if b == quote {
+            return Ok((word, &bytes[i + 1..]));
+        }
+
+        if b == b'\\' && i + 1 < bytes.len() {
+            push_escaped(&mut word, bytes[i + 1]);
+            i += 2;
+            continue;
+        }
+
+        word.push(b);
+        i += 1;
+    }
+
+    Err("unexpected end of line, expecting closing quote")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_rule_line;
+
+    fn words(line: &str) -> Vec<String> {
+        split_rule_line(line.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|word| String::from_utf8(word).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn splits_rules_on_spaces_and_skips_comments() {
+        assert_eq!(words("exclude token"), ["exclude", "token"]);
+        assert_eq!(
+            words("\texclude  ~*  ^x-amz-\r"),
+            ["exclude", "~*", "^x-amz-"]
+        );
+        assert_eq!(words("include a # trailing comment"), ["include", "a"]);
+        assert!(words("   # comment").is_empty());
+        assert!(words("").is_empty());
+    }
+
+    #[test]
+    fn quotes_and_escapes_follow_nginx() {
+        assert_eq!(
+            words(r#"exclude ~ "^a b\.c$""#),
+            ["exclude", "~", r"^a b\.c$"]
+        );
+        assert_eq!(words(r"exclude 'it\'s'"), ["exclude", "it's"]);
+        assert_eq!(words(r##"exclude "#hash""##), ["exclude", "#hash"]);
+        assert_eq!(words(r"exclude a\ b"), ["exclude", r"a\ b"]);
+    }
+
+    #[test]
+    fn rejects_semicolons_and_unbalanced_quotes() {
+        assert!(split_rule_line(b"exclude token;").is_err());
+        assert!(split_rule_line(b"exclude \"token").is_err());
+        assert!(split_rule_line(b"exclude \"a\"b").is_err());
+        assert!(split_rule_line(b"exclude \"a;b\"").is_ok());
+    }
+}
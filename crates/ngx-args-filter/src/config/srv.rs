@@ -0,0 +1,30 @@
+//! Server configuration structure.
+
+use crate::config::args_filter::ArgsFilterMap;
+use ngx::http::{Merge, MergeConfigError};
+use std::fmt;
+
+/// Module server configuration.
+#[derive(Default)]
+pub struct SrvConf {
+    /// `args_filter` definitions declared directly in this `server` block.
+    pub args_filters: Option<ArgsFilterMap>,
+}
+
+impl Merge for SrvConf {
+    fn merge(&mut self, _prev: &Self) -> Result<(), MergeConfigError> {
+        // `http`-level definitions live in `MainConf`, so there is nothing to inherit here.
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SrvConf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SrvConf")
+            .field(
+                "args_filters_count",
+                &self.args_filters.as_ref().map(|m| m.iter().count()),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,243 @@
+//! Typed value validation for `validate <key> <type> [default=<value>]`.
+//!
+//! A kept argument whose percent-decoded value does not parse as the declared
+//! type is dropped, or written out with the configured default instead.
+
+use crate::nginx_str::NginxStr;
+use ngx::collections::Vec;
+use ngx::core::Pool;
+use std::fmt;
+use tracing::error;
+
+/// Spellings accepted by `bool`, compared ignoring ASCII case.
+const BOOL_NAMES: [&[u8]; 8] = [b"true", b"false", b"1", b"0", b"yes", b"no", b"on", b"off"];
+
+/// Value type accepted by a `validate` directive.
+#[derive(Clone, Debug)]
+pub enum ValueType {
+    /// Optionally signed decimal integer that fits in 64 bits.
+    Int,
+    /// Unsigned decimal integer that fits in 64 bits.
+    Uint,
+    /// `8-4-4-4-12` hexadecimal UUID, in either case.
+    Uuid,
+    /// Non-empty run of hexadecimal digits.
+    Hex,
+    /// Standard or URL-safe base64, padded or not.
+    Base64,
+    /// `true`, `false`, `1`, `0`, `yes`, `no`, `on`, or `off`, in any case.
+    Bool,
+    /// One of the listed values, compared exactly.
+    Enum(Vec<NginxStr<Pool>, Pool>),
+    /// Integer within the inclusive bounds.
+    Range(i64, i64),
+}
+
+impl ValueType {
+    /// Parse a type as written in the `validate` directive.
+    pub fn parse(pool: &Pool, spec: &[u8]) -> Result<Self, ()> {
+        let value_type = match spec {
+            b"int" => Self::Int,
+            b"uint" => Self::Uint,
+            b"uuid" => Self::Uuid,
+            b"hex" => Self::Hex,
+            b"base64" => Self::Base64,
+            b"bool" => Self::Bool,
+            _ => {
+                if let Some(items) = arguments(spec, b"enum") {
+                    return parse_enum(pool, items);
+                }
+                if let Some(bounds) = arguments(spec, b"range") {
+                    return parse_range(bounds);
+                }
+
+                error!(
+                    r#""validate" type must be "int", "uint", "uuid", "hex", "base64", "bool", "enum(...)", or "range(min,max)""#
+                );
+                return Err(());
+            }
+        };
+
+        Ok(value_type)
+    }
+
+    /// Return true when `value` is a valid instance of this type.
+    pub fn accepts(&self, value: &[u8]) -> bool {
+        match self {
+            Self::Int => parse_int(value).is_some(),
+            Self::Uint => is_uint(value),
+            Self::Uuid => is_uuid(value),
+            Self::Hex => !value.is_empty() && value.iter().all(u8::is_ascii_hexdigit),
+            Self::Base64 => is_base64(value),
+            Self::Bool => BOOL_NAMES
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(value)),
+            Self::Enum(items) => items.iter().any(|item| item.as_bytes() == value),
+            Self::Range(min, max) => parse_int(value).is_some_and(|n| (*min..=*max).contains(&n)),
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int => f.write_str("int"),
+            Self::Uint => f.write_str("uint"),
+            Self::Uuid => f.write_str("uuid"),
+            Self::Hex => f.write_str("hex"),
+            Self::Base64 => f.write_str("base64"),
+            Self::Bool => f.write_str("bool"),
+            Self::Enum(items) => {
+                f.write_str("enum(")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str(")")
+            }
+            Self::Range(min, max) => write!(f, "range({min},{max})"),
+        }
+    }
+}
+
+/// One `validate` directive.
+#[derive(Clone, Debug)]
+pub struct Validation {
+    /// Key compared with the normalized key that rules also see.
+    pub key: NginxStr<Pool>,
+    pub value_type: ValueType,
+    /// Value written in place of an invalid one, percent-encoded like a `set`
+    /// value; without it the argument is dropped.
+    pub default: Option<NginxStr<Pool>>,
+}
+
+/// Return the text between `name(` and a final `)`.
+fn arguments<'a>(spec: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    spec.strip_prefix(name)?
+        .strip_prefix(b"(")?
+        .strip_suffix(b")")
+}
+
+fn parse_enum(pool: &Pool, items: &[u8]) -> Result<ValueType, ()> {
+    let mut values = Vec::new_in(pool.clone());
+
+    for item in items.split(|b| *b == b',') {
+        if item.is_empty() {
+            error!(r#""validate" enum values must not be empty"#);
+            return Err(());
+        }
+
+        let Ok(item) = NginxStr::from_bytes(pool.clone(), item) else {
+            error!("failed to allocate validate enum value");
+            return Err(());
+        };
+        if values.try_reserve(1).is_err() {
+            error!("failed to allocate validate enum value");
+            return Err(());
+        }
+        values.push(item);
+    }
+
+    Ok(ValueType::Enum(values))
+}
+
+fn parse_range(bounds: &[u8]) -> Result<ValueType, ()> {
+    let range = bounds.iter().position(|b| *b == b',').and_then(|comma| {
+        Some((
+            parse_int(&bounds[..comma])?,
+            parse_int(&bounds[comma + 1..])?,
+        ))
+    });
+
+    match range {
+        Some((min, max)) if min <= max => Ok(ValueType::Range(min, max)),
+        _ => {
+            error!(r#""validate" range must be "range(min,max)" with integer min <= max"#);
+            Err(())
+        }
+    }
+}
+
+fn parse_int(value: &[u8]) -> Option<i64> {
+    let digits = match value {
+        [b'-' | b'+', rest @ ..] => rest,
+        _ => value,
+    };
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
+
+fn is_uint(value: &[u8]) -> bool {
+    !value.is_empty()
+        && value.iter().all(u8::is_ascii_digit)
+        && std::str::from_utf8(value).is_ok_and(|digits| digits.parse::<u64>().is_ok())
+}
+
+fn is_uuid(value: &[u8]) -> bool {
+    value.len() == 36
+        && value.iter().enumerate().all(|(idx, b)| {
+            if matches!(idx, 8 | 13 | 18 | 23) {
+                *b == b'-'
+            } else {
+                b.is_ascii_hexdigit()
+            }
+        })
+}
+
+fn is_base64(value: &[u8]) -> bool {
+    let data = value
+        .strip_suffix(b"==")
+        .or_else(|| value.strip_suffix(b"="))
+        .unwrap_or(value);
+    let padded = data.len() != value.len();
+
+    !data.is_empty()
+        && data
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'-' | b'_'))
+        && if padded {
+            value.len().is_multiple_of(4)
+        } else {
+            data.len() % 4 != 1
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_base64, is_uint, is_uuid, parse_int};
+
+    #[test]
+    fn parses_signed_integers_within_64_bits() {
+        assert_eq!(parse_int(b"42"), Some(42));
+        assert_eq!(parse_int(b"-7"), Some(-7));
+        assert_eq!(parse_int(b"+7"), Some(7));
+        assert_eq!(parse_int(b"abc"), None);
+        assert_eq!(parse_int(b"-"), None);
+        assert_eq!(parse_int(b""), None);
+        assert_eq!(parse_int(b"1e3"), None);
+        assert_eq!(parse_int(b"99999999999999999999"), None);
+        assert!(is_uint(b"18446744073709551615"));
+        assert!(!is_uint(b"+1"));
+        assert!(!is_uint(b"18446744073709551616"));
+    }
+
+    #[test]
+    fn recognizes_uuid_and_base64_shapes() {
+        assert!(is_uuid(b"123e4567-e89b-12d3-A456-426614174000"));
+        assert!(!is_uuid(b"123e4567e89b12d3a456426614174000"));
+        assert!(!is_uuid(b"123e4567-e89b-12d3-a456-42661417400g"));
+
+        assert!(is_base64(b"aGVsbG8="));
+        assert!(is_base64(b"aGVsbG8"));
+        assert!(is_base64(b"a-_b"));
+        assert!(!is_base64(b"aGVsbG8=="));
+        assert!(!is_base64(b"a"));
+        assert!(!is_base64(b"="));
+        assert!(!is_base64(b"a b="));
+    }
+}
@@ -0,0 +1,73 @@
+//! Value length limits for `max_value_length [<key>] <length> [drop | truncate]`.
+//!
+//! Lengths are counted in raw bytes as the value appears in the query string,
+//! since that is what ends up in the upstream URL.
+
+use crate::nginx_str::NginxStr;
+use ngx::core::Pool;
+
+/// What `max_value_length` does with a longer value.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LengthPolicy {
+    /// Drop the argument.
+    #[default]
+    Drop,
+    /// Keep the argument with its value cut to the limit.
+    Truncate,
+}
+
+impl LengthPolicy {
+    /// Name as written in the `max_value_length` directive.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Drop => "drop",
+            Self::Truncate => "truncate",
+        }
+    }
+}
+
+/// One `max_value_length` directive.
+#[derive(Clone, Debug)]
+pub struct ValueLengthLimit {
+    /// Normalized key the limit applies to; `None` for the filter-wide limit.
+    pub key: Option<NginxStr<Pool>>,
+    pub max: usize,
+    pub policy: LengthPolicy,
+}
+
+/// Cut a raw query value to at most `max` bytes without splitting a `%XX`
+/// escape.
+pub fn truncate_value(value: &[u8], max: usize) -> &[u8] {
+    let mut end = 0;
+
+    while end < value.len() {
+        let escape = value[end] == b'%'
+            && value
+                .get(end + 1..end + 3)
+                .is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit));
+        let next = end + if escape { 3 } else { 1 };
+        if next > max {
+            break;
+        }
+        end = next;
+    }
+
+    &value[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::truncate_value;
+
+    #[test]
+    fn truncates_on_escape_boundaries() {
+        assert_eq!(truncate_value(b"abcdef", 4), b"abcd");
+        assert_eq!(truncate_value(b"abc", 4), b"abc");
+        assert_eq!(truncate_value(b"ab%41cd", 3), b"ab");
+        assert_eq!(truncate_value(b"ab%41cd", 4), b"ab");
+        assert_eq!(truncate_value(b"ab%41cd", 5), b"ab%41");
+        assert_eq!(truncate_value(b"%41%42", 2), b"");
+        assert_eq!(truncate_value(b"a%zz", 2), b"a%");
+        assert_eq!(truncate_value(b"a%4", 2), b"a%");
+    }
+}
@@ -0,0 +1,124 @@
+//! Shared memory key sets declared with `args_filter_zone`.
+//!
+//! Every zone holds one set of literal keys in a slab pool, so updates made
+//! through `args_filter_api` are visible to all workers on their next lookup.
+
+use crate::logging::with_request_context;
+use crate::nginx_str::NginxStr;
+use crate::status::NgxStatus;
+use ngx::allocator::AllocError;
+use ngx::collections::rbtree::RbTreeMap;
+use ngx::core::SlabPool;
+use ngx::ffi::{ngx_int_t, ngx_shm_zone_t};
+use ngx::sync::RwLock;
+use tracing::error;
+
+/// Key set stored at the start of the zone's slab pool.
+type ZoneKeys = RwLock<RbTreeMap<NginxStr<SlabPool>, (), SlabPool>>;
+
+/// Handle to a shared memory zone registered by `args_filter_zone`.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyZone {
+    pub zone: *mut ngx_shm_zone_t,
+}
+
+impl KeyZone {
+    /// Return true when `key` is currently stored in the zone.
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.keys()
+            .is_some_and(|keys| keys.read().get(key).is_some())
+    }
+
+    /// Store `key`; returns false when it was already present.
+    pub fn insert(&self, key: &[u8]) -> Result<bool, AllocError> {
+        let Some(keys) = self.keys() else {
+            return Err(AllocError);
+        };
+
+        let mut keys = keys.write();
+        if keys.get(key).is_some() {
+            return Ok(false);
+        }
+
+        let key = NginxStr::from_bytes(keys.allocator().clone(), key).map_err(|_| AllocError)?;
+        keys.try_insert(key, ())?;
+        Ok(true)
+    }
+
+    /// Remove `key`; returns false when it was not present.
+    pub fn remove(&self, key: &[u8]) -> bool {
+        self.keys()
+            .is_some_and(|keys| keys.write().remove(key).is_some())
+    }
+
+    /// Copy the stored keys out of shared memory, sorted bytewise.
+    pub fn snapshot(&self) -> std::vec::Vec<std::vec::Vec<u8>> {
+        let Some(keys) = self.keys() else {
+            return std::vec::Vec::new();
+        };
+
+        let mut snapshot: std::vec::Vec<_> = keys
+            .read()
+            .iter()
+            .map(|(key, _)| key.as_bytes().to_vec())
+            .collect();
+        snapshot.sort_unstable();
+        snapshot
+    }
+
+    /// Zone name as declared in `args_filter_zone`.
+    pub fn name(&self) -> String {
+        let name = unsafe { (*self.zone).shm.name };
+        String::from_utf8_lossy(unsafe { std::slice::from_raw_parts(name.data, name.len) })
+            .into_owned()
+    }
+
+    fn keys(&self) -> Option<&ZoneKeys> {
+        // `data` is set by `args_filter_zone_init` before workers start.
+        unsafe { self.zone.as_ref()?.data.cast::<ZoneKeys>().as_ref() }
+    }
+}
+
+/// Shared zone init callback: create the key set, or adopt the previous
+/// cycle's set so stored keys survive a configuration reload.
+///
+/// # Safety
+///
+/// Called by nginx with a mapped shared memory zone.
+pub unsafe extern "C" fn args_filter_zone_init(
+    shm_zone: *mut ngx_shm_zone_t,
+    data: *mut core::ffi::c_void,
+) -> ngx_int_t {
+    let shm_zone = unsafe { &mut *shm_zone };
+
+    with_request_context(shm_zone.shm.log, || {
+        if !data.is_null() {
+            shm_zone.data = data;
+            return NgxStatus::OK;
+        }
+
+        let Some(mut pool) = (unsafe { SlabPool::from_shm_zone(shm_zone) }) else {
+            error!("args_filter_zone: shared memory is not mapped");
+            return NgxStatus::ERROR;
+        };
+
+        if shm_zone.shm.exists != 0 {
+            shm_zone.data = pool.as_ref().data;
+            return NgxStatus::OK;
+        }
+
+        let Ok(map) = RbTreeMap::try_new_in(pool.clone()) else {
+            error!("args_filter_zone: failed to allocate key set");
+            return NgxStatus::ERROR;
+        };
+
+        let Ok(keys) = ngx::allocator::allocate(ZoneKeys::new(map), &pool) else {
+            error!("args_filter_zone: failed to allocate key set");
+            return NgxStatus::ERROR;
+        };
+
+        pool.as_mut().data = keys.as_ptr().cast();
+        shm_zone.data = keys.as_ptr().cast();
+        NgxStatus::OK
+    })
+}
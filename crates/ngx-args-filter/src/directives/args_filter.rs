@@ -4,22 +4,39 @@
 
 use crate::NgxArgsFilterModule;
 use crate::conf_ext::NgxConfExt;
-use crate::config::args_filter::{ArgsFilterDef, ArgsFilterVarData};
+use crate::config::MainConf;
+use crate::config::args_filter::{
+    ArgsFilterDef, ArgsFilterMap, ArgsFilterVarData, EmptyValuePolicy, InvalidEscapePolicy,
+    RuleState, ValuelessKeyPolicy, evaluate_complex_value,
+};
+use crate::config::main::{FilterScope, ScopedFilters};
+use crate::config::regex_limits::{RegexLimitExceeded, RegexLimitPolicy};
+use crate::config::validate::ValueType;
+use crate::config::value_length::{LengthPolicy, truncate_value};
 use crate::logging::{with_config_context, with_request_context};
 use crate::nginx_str::NginxStr;
 use crate::status::NgxStatus;
 use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK};
 use ngx::ffi::{
-    NGX_CONF_BLOCK, NGX_CONF_TAKE1, NGX_HTTP_MAIN_CONF, ngx_command_t, ngx_conf_t,
-    ngx_http_add_variable, ngx_http_variable_value_t, ngx_int_t, ngx_pcalloc, ngx_pnalloc,
+    NGX_CONF_BLOCK, NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_MAIN_CONF, NGX_HTTP_SRV_CONF,
+    ngx_command_t, ngx_conf_t, ngx_http_add_variable, ngx_http_variable_value_t, ngx_int_t,
+    ngx_pcalloc, ngx_pnalloc, ngx_uint_t,
+};
+use ngx::http::{
+    HttpModuleLocationConf, HttpModuleMainConf, HttpModuleServerConf, NgxHttpCoreModule,
 };
-use ngx::http::HttpModuleMainConf;
-use tracing::{debug, error};
+use std::borrow::Cow;
+use std::ptr::NonNull;
+use tracing::{debug, error, warn};
 
 #[unsafe(no_mangle)]
 pub static mut ARGS_FILTER_COMMAND: ngx_command_t = ngx_command_t {
     name: ngx::ngx_string!("args_filter"),
-    type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_BLOCK | NGX_CONF_TAKE1) as _,
+    type_: (NGX_HTTP_MAIN_CONF
+        | NGX_HTTP_SRV_CONF
+        | NGX_HTTP_LOC_CONF
+        | NGX_CONF_BLOCK
+        | NGX_CONF_TAKE1) as _,
     set: Some(args_filter_set),
     conf: 0,
     offset: 0,
@@ -34,53 +51,47 @@ extern "C" fn args_filter_set(
 ) -> *mut core::ffi::c_char {
     with_config_context(cf, || {
         let cf_ref = unsafe { cf.as_mut().expect("cf") };
-        let main_conf = unsafe {
-            conf.cast::<crate::config::MainConf>()
-                .as_mut()
-                .expect("main_conf")
-        };
+        let main_conf = unsafe { conf.cast::<MainConf>().as_mut().expect("main_conf") };
         let args = cf_ref.args();
 
         let Ok(var_name) = parse_variable_name(cf_ref, &args[1]) else {
             return NGX_CONF_ERROR;
         };
 
-        if main_conf.args_filters.is_none() {
-            let Ok(map) = ngx::collections::RbTreeMap::try_new_in(cf_ref.pool()) else {
-                error!("failed to initialize args_filter map");
-                return NGX_CONF_ERROR;
-            };
-            main_conf.args_filters = Some(map);
+        if unsafe { ensure_variable_registered(cf, main_conf, &var_name) }.is_err() {
+            return NGX_CONF_ERROR;
         }
 
-        let Some(filters_map) = main_conf.args_filters.as_ref() else {
-            error!("args_filter map unavailable");
-            return NGX_CONF_ERROR;
-        };
+        let created = {
+            let Some(scope_filters) = scope_filters(cf_ref, main_conf) else {
+                error!("args_filter configuration for current context unavailable");
+                return NGX_CONF_ERROR;
+            };
 
-        if filters_map.get(var_name.as_bytes()).is_some() {
-            error!("duplicate args_filter declaration for ${}", var_name);
-            return NGX_CONF_ERROR;
-        }
+            let created = scope_filters.is_none();
+            if created {
+                let Ok(map) = ngx::collections::RbTreeMap::try_new_in(cf_ref.pool()) else {
+                    error!("failed to initialize args_filter map");
+                    return NGX_CONF_ERROR;
+                };
+                *scope_filters = Some(map);
+            }
 
-        let mut var_name_ngx = var_name.as_ngx_str();
-        let var = unsafe { ngx_http_add_variable(cf, &raw mut var_name_ngx, 0) };
-        if var.is_null() {
-            error!("failed to register variable ${}", var_name);
-            return NGX_CONF_ERROR;
-        }
+            if scope_filters
+                .as_ref()
+                .is_some_and(|m| m.get(var_name.as_bytes()).is_some())
+            {
+                error!("duplicate args_filter declaration for ${}", var_name);
+                return NGX_CONF_ERROR;
+            }
+            created
+        };
 
-        let var_data = unsafe { allocate_var_data(cf, &var_name) };
-        if var_data.is_null() {
-            error!("failed to allocate args_filter variable metadata");
+        if created && record_scope(cf_ref, main_conf).is_err() {
+            error!("args_filter configuration for current context unavailable");
             return NGX_CONF_ERROR;
         }
 
-        unsafe {
-            (*var).get_handler = Some(args_filter_variable_get_handler);
-            (*var).data = var_data.cast::<ArgsFilterVarData>() as _;
-        }
-
         let mut filter = ArgsFilterDef::new();
 
         let mut block_cf = *cf_ref;
@@ -92,7 +103,23 @@ extern "C" fn args_filter_set(
             return rv;
         }
 
-        let Some(filters_map_mut) = main_conf.args_filters.as_mut() else {
+        if report_lint_findings(main_conf.strict, &var_name, &filter).is_err() {
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.index_rule_runs(cf).is_err() {
+            error!("failed to index args_filter rules");
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.regex_limits.prepare(&cf_ref.pool()).is_err() {
+            error!("failed to create args_filter regex match context");
+            return NGX_CONF_ERROR;
+        }
+
+        let Some(filters_map_mut) =
+            scope_filters(cf_ref, main_conf).and_then(|filters| filters.as_mut())
+        else {
             error!("args_filter map unavailable after parse");
             return NGX_CONF_ERROR;
         };
@@ -183,16 +210,7 @@ pub extern "C" fn args_filter_variable_get_handler(
             unsafe { std::slice::from_raw_parts((*var_data).name.data, (*var_data).name.len) };
         let var_name = String::from_utf8_lossy(name_slice);
 
-        let Some(main_conf) = NgxArgsFilterModule::main_conf(req) else {
-            error!("failed to fetch module main conf in variable handler");
-            return mark_not_found(v);
-        };
-
-        let Some(filters) = main_conf.args_filters.as_ref() else {
-            return mark_not_found(v);
-        };
-
-        let Some(filter) = filters.get(name_slice) else {
+        let Some(filter) = find_filter(req, name_slice) else {
             return mark_not_found(v);
         };
 
@@ -217,7 +235,24 @@ pub extern "C" fn args_filter_variable_get_handler(
             return unsafe { set_variable_value(r, v, args, filter.volatile) };
         }
 
-        let filtered = filter_args_by(args, |key| filter.should_keep_key(key));
+        let dynamic = filter.evaluate_dynamic(req);
+        // Values are only decoded when some rule or validation looks at them.
+        let decode_values = filter.has_value_rules() || filter.validations.is_some();
+        let mut limit_exceeded = false;
+        let mut filtered = filter_args_by(args, |key, value| {
+            filter_segment(filter, key, value, decode_values, &dynamic).unwrap_or_else(|_| {
+                limit_exceeded = true;
+                SegmentAction::Drop
+            })
+        });
+        if limit_exceeded {
+            error!(
+                "args_filter: variable='${}' failed: a rule regex stopped at a PCRE limit",
+                var_name
+            );
+            return mark_not_found(v);
+        }
+        append_injected_args(filter, req, &mut filtered);
         debug!(
             "args_filter: variable='${}' filtered result='{}'",
             var_name,
@@ -227,7 +262,161 @@ pub extern "C" fn args_filter_variable_get_handler(
     })
 }
 
-fn parse_variable_name(
+/// Log lint findings for a parsed filter; under `args_filter_strict on` they fail the config.
+fn report_lint_findings(
+    strict: bool,
+    var_name: &NginxStr<ngx::core::Pool>,
+    filter: &ArgsFilterDef,
+) -> Result<(), ()> {
+    let findings = filter.lint();
+
+    for finding in &findings {
+        if strict {
+            error!("args_filter ${}: {}", var_name, finding);
+        } else {
+            warn!("args_filter ${}: {}", var_name, finding);
+        }
+    }
+
+    if strict && !findings.is_empty() {
+        return Err(());
+    }
+
+    Ok(())
+}
+
+/// Return the target map for an `args_filter` block in the current nginx context.
+fn scope_filters<'a>(
+    cf: &ngx_conf_t,
+    main_conf: &'a mut MainConf,
+) -> Option<&'a mut Option<ArgsFilterMap>> {
+    if cf.cmd_type == NGX_HTTP_LOC_CONF as ngx_uint_t {
+        return NgxArgsFilterModule::location_conf_mut(cf).map(|c| &mut c.args_filters);
+    }
+
+    if cf.cmd_type == NGX_HTTP_SRV_CONF as ngx_uint_t {
+        return NgxArgsFilterModule::server_conf_mut(cf).map(|c| &mut c.args_filters);
+    }
+
+    Some(&mut main_conf.args_filters)
+}
+
+/// Record a newly created `server` or `location` filter map for `args_filter_dump`.
+fn record_scope(cf: &ngx_conf_t, main_conf: &mut MainConf) -> Result<(), ()> {
+    let scope = if cf.cmd_type == NGX_HTTP_LOC_CONF as ngx_uint_t {
+        FilterScope::Location(NonNull::from(
+            NgxHttpCoreModule::location_conf(cf).ok_or(())?,
+        ))
+    } else if cf.cmd_type == NGX_HTTP_SRV_CONF as ngx_uint_t {
+        FilterScope::Server(NonNull::from(NgxHttpCoreModule::server_conf(cf).ok_or(())?))
+    } else {
+        return Ok(());
+    };
+
+    let filters = scope_filters(cf, main_conf)
+        .and_then(|filters| filters.as_ref())
+        .map(NonNull::from)
+        .ok_or(())?;
+    main_conf.add_scoped_filters(cf.pool(), ScopedFilters { scope, filters });
+    Ok(())
+}
+
+/// Resolve an already declared filter visible from the block being parsed: the
+/// current location and the locations enclosing it, then the enclosing
+/// `server`, then `http`.
+pub fn find_declared_filter(cf: &ngx_conf_t, name: &[u8]) -> Option<&'static ArgsFilterDef> {
+    if let Some(filter) = NgxArgsFilterModule::location_conf(cf).and_then(|c| c.find_filter(name)) {
+        return Some(filter);
+    }
+
+    if let Some(filter) = NgxArgsFilterModule::server_conf(cf)
+        .and_then(|c| c.args_filters.as_ref())
+        .and_then(|m| m.get(name))
+    {
+        return Some(filter);
+    }
+
+    NgxArgsFilterModule::main_conf(cf)
+        .and_then(|c| c.args_filters.as_ref())
+        .and_then(|m| m.get(name))
+}
+
+/// Resolve the innermost definition of `name`: location chain, then server, then `http`.
+fn find_filter<'a>(req: &'a ngx::http::Request, name: &[u8]) -> Option<&'a ArgsFilterDef> {
+    if let Some(filter) = NgxArgsFilterModule::location_conf(req).and_then(|c| c.find_filter(name))
+    {
+        return Some(filter);
+    }
+
+    if let Some(filter) = NgxArgsFilterModule::server_conf(req)
+        .and_then(|c| c.args_filters.as_ref())
+        .and_then(|m| m.get(name))
+    {
+        return Some(filter);
+    }
+
+    NgxArgsFilterModule::main_conf(req)
+        .and_then(|c| c.args_filters.as_ref())
+        .and_then(|m| m.get(name))
+}
+
+/// Register `$name` with nginx once, regardless of how many contexts define it.
+unsafe fn ensure_variable_registered(
+    cf: *mut ngx_conf_t,
+    main_conf: &mut MainConf,
+    var_name: &NginxStr<ngx::core::Pool>,
+) -> Result<(), ()> {
+    let pool = unsafe { ngx::core::Pool::from_ngx_pool((*cf).pool) };
+
+    if main_conf.variables.is_none() {
+        let Ok(map) = ngx::collections::RbTreeMap::try_new_in(pool.clone()) else {
+            error!("failed to initialize args_filter variable registry");
+            return Err(());
+        };
+        main_conf.variables = Some(map);
+    }
+
+    let Some(variables) = main_conf.variables.as_mut() else {
+        error!("args_filter variable registry unavailable");
+        return Err(());
+    };
+
+    if variables.get(var_name.as_bytes()).is_some() {
+        return Ok(());
+    }
+
+    let mut var_name_ngx = var_name.as_ngx_str();
+    let var = unsafe { ngx_http_add_variable(cf, &raw mut var_name_ngx, 0) };
+    if var.is_null() {
+        error!("failed to register variable ${}", var_name);
+        return Err(());
+    }
+
+    let var_data = unsafe { allocate_var_data(cf, var_name) };
+    if var_data.is_null() {
+        error!("failed to allocate args_filter variable metadata");
+        return Err(());
+    }
+
+    unsafe {
+        (*var).get_handler = Some(args_filter_variable_get_handler);
+        (*var).data = var_data.cast::<ArgsFilterVarData>() as _;
+    }
+
+    let Ok(registered_name) = NginxStr::from_bytes(pool, var_name.as_bytes()) else {
+        error!("failed to allocate args_filter variable name");
+        return Err(());
+    };
+
+    if variables.try_insert(registered_name, ()).is_err() {
+        error!("failed to record args_filter variable registration");
+        return Err(());
+    }
+
+    Ok(())
+}
+
+pub fn parse_variable_name(
     cf: &ngx_conf_t,
     raw: &ngx::ffi::ngx_str_t,
 ) -> Result<NginxStr<ngx::core::Pool>, ()> {
@@ -275,9 +464,378 @@ unsafe fn allocate_var_data(
     data
 }
 
-fn filter_args_by<F>(args: &[u8], mut keep_key: F) -> std::vec::Vec<u8>
+/// What [`filter_args_by`] does with one `key=value` segment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum SegmentAction<'a> {
+    Drop,
+    Keep,
+    /// Keep the key, written with this raw value.
+    ReplaceValue(&'a [u8]),
+    /// Write the segment under this raw key, with this raw value or, when
+    /// `None`, with its own.
+    Rename(std::vec::Vec<u8>, Option<&'a [u8]>),
+}
+
+impl From<bool> for SegmentAction<'_> {
+    fn from(keep: bool) -> Self {
+        if keep { Self::Keep } else { Self::Drop }
+    }
+}
+
+/// Decide one segment, percent-decoding its key first when the filter has
+/// `decode_keys on` and reducing it to its base name under `array_keys on`.
+///
+/// `value` is the raw value, `None` for a segment without `=`. Empty and
+/// missing values are handled by `empty_values` and `valueless_keys` before
+/// any rule runs. A kept segment's value is then held to `max_value_length`,
+/// and what remains is checked against the `validate` directive for the key.
+/// Rules and validations see the value percent-decoded when `decode_values`
+/// is set. Finally the segment is renamed by the first matching `rename`, and
+/// dropped when a `set` replaces its key.
+fn filter_segment<'a>(
+    filter: &'a ArgsFilterDef,
+    raw_key: &[u8],
+    value: Option<&'a [u8]>,
+    decode_values: bool,
+    dynamic: &[RuleState<'_>],
+) -> Result<SegmentAction<'a>, RegexLimitExceeded> {
+    let (mut raw, mut kept) = match value {
+        None => match filter.valueless_keys {
+            ValuelessKeyPolicy::Keep => (&[][..], SegmentAction::Keep),
+            ValuelessKeyPolicy::Drop => return Ok(SegmentAction::Drop),
+            ValuelessKeyPolicy::AsEmpty => (&[][..], SegmentAction::ReplaceValue(&[])),
+        },
+        Some([]) if filter.empty_values == EmptyValuePolicy::Drop => {
+            return Ok(SegmentAction::Drop);
+        }
+        Some(value) => (value, SegmentAction::Keep),
+    };
+
+    let key = if filter.decode_keys {
+        let (decoded, valid) = decode_component(raw_key);
+        if !valid {
+            debug!(
+                "args_filter: key='{}' has invalid percent escapes; policy={:?}",
+                String::from_utf8_lossy(raw_key),
+                filter.invalid_escapes
+            );
+
+            match filter.invalid_escapes {
+                InvalidEscapePolicy::Literal => {}
+                InvalidEscapePolicy::Drop => return Ok(SegmentAction::Drop),
+                InvalidEscapePolicy::Keep => return Ok(kept),
+            }
+        }
+        decoded
+    } else {
+        Cow::Borrowed(raw_key)
+    };
+
+    let key: &[u8] = if filter.array_keys {
+        array_base_name(&key)
+    } else {
+        &key
+    };
+
+    let decode = |raw| {
+        if decode_values {
+            decode_component(raw).0
+        } else {
+            Cow::Borrowed(raw)
+        }
+    };
+
+    let mut value = decode(raw);
+    if !filter.should_keep_key(key, &value, dynamic)? {
+        return Ok(SegmentAction::Drop);
+    }
+
+    if let Some(limit) = filter.find_value_length_limit(key)
+        && raw.len() > limit.max
+    {
+        debug!(
+            "args_filter: key='{}' value is longer than {} bytes; policy={}",
+            String::from_utf8_lossy(key),
+            limit.max,
+            limit.policy.name()
+        );
+
+        if limit.policy == LengthPolicy::Drop {
+            return Ok(SegmentAction::Drop);
+        }
+        raw = truncate_value(raw, limit.max);
+        value = decode(raw);
+        kept = SegmentAction::ReplaceValue(raw);
+    }
+
+    if let Some(validation) = filter.find_validation(key)
+        && !validation.value_type.accepts(&match validation.value_type {
+            // `+` is a base64 digit, so only `%XX` escapes are decoded.
+            ValueType::Base64 => decode_escapes(raw, false).0,
+            _ => Cow::Borrowed(&*value),
+        })
+    {
+        debug!(
+            "args_filter: key='{}' value is not a valid {}; default={:?}",
+            String::from_utf8_lossy(key),
+            validation.value_type,
+            validation.default
+        );
+
+        let Some(default) = validation.default.as_ref() else {
+            return Ok(SegmentAction::Drop);
+        };
+        kept = SegmentAction::ReplaceValue(default.as_bytes());
+    }
+
+    let action = rename_segment(filter, raw_key, key, kept)?;
+    if is_replaced_by_set(filter, key, &action) {
+        debug!(
+            "args_filter: key='{}' is replaced by \"set\"",
+            String::from_utf8_lossy(raw_key)
+        );
+        return Ok(SegmentAction::Drop);
+    }
+
+    Ok(action)
+}
+
+/// Return true when a `set` directive declares the key that `action` writes
+/// for a segment with the normalized `key`.
+fn is_replaced_by_set(filter: &ArgsFilterDef, key: &[u8], action: &SegmentAction<'_>) -> bool {
+    let mut set_keys = filter
+        .set_args()
+        .map(|arg| normalize_key(filter, arg.key.as_bytes()))
+        .peekable();
+    if set_keys.peek().is_none() {
+        return false;
+    }
+
+    let written = match action {
+        SegmentAction::Rename(renamed, _) => normalize_key(filter, renamed),
+        _ => Cow::Borrowed(key),
+    };
+    set_keys.any(|set_key| set_key == written)
+}
+
+/// Reduce `raw_key` the way keys are reduced before rules see them: decoded
+/// under `decode_keys on` and stripped of its index under `array_keys on`.
+fn normalize_key<'k>(filter: &ArgsFilterDef, raw_key: &'k [u8]) -> Cow<'k, [u8]> {
+    let key = if filter.decode_keys {
+        decode_component(raw_key).0
+    } else {
+        Cow::Borrowed(raw_key)
+    };
+
+    if !filter.array_keys {
+        return key;
+    }
+
+    match key {
+        Cow::Borrowed(key) => Cow::Borrowed(array_base_name(key)),
+        Cow::Owned(key) => Cow::Owned(array_base_name(&key).to_vec()),
+    }
+}
+
+/// Append the `set` and `add` arguments of `filter` to `output` in declaration
+/// order, with their values evaluated for `req` and percent-encoded.
+fn append_injected_args(
+    filter: &ArgsFilterDef,
+    req: &ngx::http::Request,
+    output: &mut std::vec::Vec<u8>,
+) {
+    for arg in filter.injected_args.iter().flatten() {
+        let Some(value) = evaluate_complex_value(req, arg.value) else {
+            continue;
+        };
+
+        if !output.is_empty() {
+            output.push(b'&');
+        }
+        output.extend_from_slice(arg.key.as_bytes());
+        output.push(b'=');
+        escape_component(value, output);
+    }
+}
+
+/// Give a kept segment the name from the first `rename` matching its
+/// normalized `key`.
+///
+/// Under `array_keys on` the index suffix of `raw_key` is carried over, and
+/// under `decode_keys on` captured text is percent-encoded again. When a
+/// rename regex stops at a PCRE limit, `regex_limit_exceeded` decides whether
+/// the segment keeps its name, is dropped, or fails the variable.
+fn rename_segment<'a>(
+    filter: &ArgsFilterDef,
+    raw_key: &[u8],
+    key: &[u8],
+    kept: SegmentAction<'a>,
+) -> Result<SegmentAction<'a>, RegexLimitExceeded> {
+    if filter.renames.is_none() {
+        return Ok(kept);
+    }
+
+    let mut renamed = std::vec::Vec::new();
+    let push_capture = |out: &mut std::vec::Vec<u8>, text: &[u8]| {
+        if filter.decode_keys {
+            escape_component(text, out);
+        } else {
+            escape_raw_component(text, out);
+        }
+    };
+
+    // A replacement that expands to nothing leaves the key as it was.
+    match filter.rename_key(key, &mut renamed, push_capture) {
+        Ok(true) if !renamed.is_empty() => {}
+        Ok(_) => return Ok(kept),
+        Err(limit) => {
+            let policy = filter.regex_limits.exceeded;
+            warn!(
+                "args_filter: rename of key='{}' stopped at a regex limit; policy={}",
+                String::from_utf8_lossy(key),
+                policy.name()
+            );
+
+            return match policy {
+                RegexLimitPolicy::Keep => Ok(kept),
+                RegexLimitPolicy::Drop => Ok(SegmentAction::Drop),
+                RegexLimitPolicy::Fail => Err(limit),
+            };
+        }
+    }
+
+    if filter.array_keys {
+        renamed.extend_from_slice(&raw_key[array_base_name(raw_key).len()..]);
+    }
+
+    debug!(
+        "args_filter: key='{}' renamed to '{}'",
+        String::from_utf8_lossy(raw_key),
+        String::from_utf8_lossy(&renamed)
+    );
+    Ok(match kept {
+        SegmentAction::ReplaceValue(value) => SegmentAction::Rename(renamed, Some(value)),
+        _ => SegmentAction::Rename(renamed, None),
+    })
+}
+
+/// Strip a trailing `[...]` index, raw or percent-encoded, from an array-style key.
+///
+/// `ids[]`, `ids[0]`, `ids%5B%5D`, and `ids[a][b]` all reduce to `ids`; keys
+/// without a closing bracket or with an empty base name are returned unchanged.
+fn array_base_name(key: &[u8]) -> &[u8] {
+    let closed = key.ends_with(b"]") || key.ends_with(b"%5D") || key.ends_with(b"%5d");
+    if !closed {
+        return key;
+    }
+
+    let open = (0..key.len()).find(|&idx| {
+        let rest = &key[idx..];
+        rest.starts_with(b"[") || rest.starts_with(b"%5B") || rest.starts_with(b"%5b")
+    });
+
+    match open {
+        Some(pos) if pos > 0 => &key[..pos],
+        _ => key,
+    }
+}
+
+/// Decode `%XX` escapes and `+` in a query key or value.
+///
+/// Malformed escapes are copied unchanged; the flag is false when any were found.
+fn decode_component(component: &[u8]) -> (Cow<'_, [u8]>, bool) {
+    decode_escapes(component, true)
+}
+
+/// Decode `%XX` escapes in a query key or value, and `+` as a space when
+/// `plus_as_space` is set.
+fn decode_escapes(component: &[u8], plus_as_space: bool) -> (Cow<'_, [u8]>, bool) {
+    if !component
+        .iter()
+        .any(|b| *b == b'%' || (plus_as_space && *b == b'+'))
+    {
+        return (Cow::Borrowed(component), true);
+    }
+
+    let mut decoded = std::vec::Vec::with_capacity(component.len());
+    let mut valid = true;
+    let mut idx = 0;
+
+    while idx < component.len() {
+        match component[idx] {
+            b'+' if plus_as_space => decoded.push(b' '),
+            b'%' => {
+                let escape = component
+                    .get(idx + 1..idx + 3)
+                    .and_then(|hex| Some((hex_value(hex[0])? << 4) | hex_value(hex[1])?));
+
+                if let Some(byte) = escape {
+                    decoded.push(byte);
+                    idx += 3;
+                    continue;
+                }
+
+                valid = false;
+                decoded.push(b'%');
+            }
+            byte => decoded.push(byte),
+        }
+        idx += 1;
+    }
+
+    (Cow::Owned(decoded), valid)
+}
+
+/// Percent-encode the bytes of `component` that would change the meaning of a
+/// query string, appending the result to `out`.
+pub fn escape_component(component: &[u8], out: &mut std::vec::Vec<u8>) {
+    for &byte in component {
+        if byte <= b' ' || byte >= 0x7f || matches!(byte, b'%' | b'&' | b'=' | b'#' | b'+') {
+            push_escaped(byte, out);
+        } else {
+            out.push(byte);
+        }
+    }
+}
+
+/// Like [`escape_component`] for text taken from the raw query string: its
+/// `%XX` escapes and `+` are already encoded and are kept as they are.
+fn escape_raw_component(component: &[u8], out: &mut std::vec::Vec<u8>) {
+    for &byte in component {
+        if byte <= b' ' || byte >= 0x7f || matches!(byte, b'&' | b'=' | b'#') {
+            push_escaped(byte, out);
+        } else {
+            out.push(byte);
+        }
+    }
+}
+
+fn push_escaped(byte: u8, out: &mut std::vec::Vec<u8>) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    out.extend_from_slice(&[
+        b'%',
+        HEX[usize::from(byte >> 4)],
+        HEX[usize::from(byte & 0xf)],
+    ]);
+}
+
+const fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Rebuild `args` from the `&`-separated segments, each handled as
+/// `decide(key, value)` says.
+///
+/// `value` is the raw text after the first `=`, or `None` when the segment has none.
+fn filter_args_by<'a, F>(args: &'a [u8], mut decide: F) -> std::vec::Vec<u8>
 where
-    F: FnMut(&[u8]) -> bool,
+    F: FnMut(&'a [u8], Option<&'a [u8]>) -> SegmentAction<'a>,
 {
     let mut output = std::vec::Vec::with_capacity(args.len());
 
@@ -291,15 +849,34 @@ where
             .position(|b| *b == b'=')
             .unwrap_or(segment.len());
         let key = &segment[..key_len];
+        let value = segment.get(key_len + 1..);
 
-        if !keep_key(key) {
+        let action = decide(key, value);
+        if action == SegmentAction::Drop {
             continue;
         }
 
         if !output.is_empty() {
             output.push(b'&');
         }
-        output.extend_from_slice(segment);
+
+        match action {
+            SegmentAction::ReplaceValue(value) => {
+                output.extend_from_slice(key);
+                output.push(b'=');
+                output.extend_from_slice(value);
+            }
+            SegmentAction::Rename(key, Some(value)) => {
+                output.extend_from_slice(&key);
+                output.push(b'=');
+                output.extend_from_slice(value);
+            }
+            SegmentAction::Rename(key, None) => {
+                output.extend_from_slice(&key);
+                output.extend_from_slice(&segment[key_len..]);
+            }
+            SegmentAction::Drop | SegmentAction::Keep => output.extend_from_slice(segment),
+        }
     }
 
     output
@@ -356,27 +933,120 @@ fn mark_not_found(v: *mut ngx_http_variable_value_t) -> ngx_int_t {
 
 #[cfg(test)]
 mod tests {
-    use super::filter_args_by;
+    use super::{
+        SegmentAction, array_base_name, decode_component, decode_escapes, escape_component,
+        escape_raw_component, filter_args_by,
+    };
 
     #[test]
     fn filter_args_keeps_expected_keys() {
-        let out = filter_args_by(b"x=1&ads.foo=2&ads.test=3&y=4", |k| {
-            k == b"x" || k == b"ads.test" || k == b"y"
+        let out = filter_args_by(b"x=1&ads.foo=2&ads.test=3&y=4", |k, _| {
+            (k == b"x" || k == b"ads.test" || k == b"y").into()
         });
         assert_eq!(out, b"x=1&ads.test=3&y=4");
     }
 
     #[test]
     fn filter_args_handles_missing_values_and_separators() {
-        let out = filter_args_by(b"&&a&b=2&&c", |k| k == b"a" || k == b"c");
+        let out = filter_args_by(b"&&a&b=2&&c", |k, _| (k == b"a" || k == b"c").into());
         assert_eq!(out, b"a&c");
     }
 
     #[test]
     fn filter_args_preserves_percent_encoded_plus_bytes() {
-        let out = filter_args_by(b"keep=%2B&drop=x+y&keep2=a%2Bb", |k| {
-            k == b"keep" || k == b"keep2"
+        let out = filter_args_by(b"keep=%2B&drop=x+y&keep2=a%2Bb", |k, _| {
+            (k == b"keep" || k == b"keep2").into()
         });
         assert_eq!(out, b"keep=%2B&keep2=a%2Bb");
     }
+
+    #[test]
+    fn decode_component_decodes_escapes_and_plus() {
+        let (decoded, valid) = decode_component(b"ac%63ess_token+x%5B%5d");
+        assert_eq!(&*decoded, b"access_token x[]");
+        assert!(valid);
+    }
+
+    #[test]
+    fn decode_escapes_can_keep_plus() {
+        let (decoded, valid) = decode_escapes(b"ab+c%2Fd%3D", false);
+        assert_eq!(&*decoded, b"ab+c/d=");
+        assert!(valid);
+    }
+
+    #[test]
+    fn decode_component_copies_invalid_escapes_unchanged() {
+        let (decoded, valid) = decode_component(b"a%zzb%4");
+        assert_eq!(&*decoded, b"a%zzb%4");
+        assert!(!valid);
+    }
+
+    #[test]
+    fn filter_args_passes_raw_values() {
+        let out = filter_args_by(b"a=1&cb=x%3By&flag&e=", |_, v| {
+            (v != Some(&b"x%3By"[..])).into()
+        });
+        assert_eq!(out, b"a=1&flag&e=");
+    }
+
+    #[test]
+    fn filter_args_tells_missing_from_empty_values() {
+        let out = filter_args_by(b"a&b=&c=1", |_, v| {
+            v.map_or(SegmentAction::ReplaceValue(b""), |v| (!v.is_empty()).into())
+        });
+        assert_eq!(out, b"a=&c=1");
+    }
+
+    #[test]
+    fn filter_args_replaces_values() {
+        let out = filter_args_by(b"limit=abc&q=x&flag", |k, _| {
+            if k == b"q" {
+                SegmentAction::Keep
+            } else {
+                SegmentAction::ReplaceValue(b"10")
+            }
+        });
+        assert_eq!(out, b"limit=10&q=x&flag=10");
+    }
+
+    #[test]
+    fn filter_args_renames_keys() {
+        let out = filter_args_by(b"aws.id=1&flag&q=x", |k, _| match k {
+            b"aws.id" => SegmentAction::Rename(b"id".to_vec(), None),
+            b"flag" => SegmentAction::Rename(b"f".to_vec(), Some(b"1")),
+            _ => SegmentAction::Keep,
+        });
+        assert_eq!(out, b"id=1&f=1&q=x");
+    }
+
+    #[test]
+    fn escape_component_encodes_query_delimiters() {
+        let mut out = std::vec::Vec::new();
+        escape_component("a b&c=d+e%f#g/h\u{e9}".as_bytes(), &mut out);
+        assert_eq!(out, b"a%20b%26c%3Dd%2Be%25f%23g/h%C3%A9");
+    }
+
+    #[test]
+    fn escape_raw_component_keeps_existing_escapes() {
+        let mut out = std::vec::Vec::new();
+        escape_raw_component("a%20b+c#d\u{e9}".as_bytes(), &mut out);
+        assert_eq!(out, b"a%20b+c%23d%C3%A9");
+    }
+
+    #[test]
+    fn array_base_name_strips_raw_and_encoded_indexes() {
+        assert_eq!(array_base_name(b"ids[]"), b"ids");
+        assert_eq!(array_base_name(b"ids[0]"), b"ids");
+        assert_eq!(array_base_name(b"ids%5B%5D"), b"ids");
+        assert_eq!(array_base_name(b"ids%5b0%5d"), b"ids");
+        assert_eq!(array_base_name(b"user[name][first]"), b"user");
+    }
+
+    #[test]
+    fn array_base_name_keeps_non_array_keys() {
+        assert_eq!(array_base_name(b"ids"), b"ids");
+        assert_eq!(array_base_name(b"ids["), b"ids[");
+        assert_eq!(array_base_name(b"[]"), b"[]");
+        assert_eq!(array_base_name(b"a]"), b"a]");
+    }
 }
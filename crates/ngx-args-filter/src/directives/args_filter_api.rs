@@ -0,0 +1,157 @@
+//! `args_filter_api` content handler.
+//!
+//! Lists, adds, and removes keys of an `args_filter_zone` at runtime:
+//! `GET ?zone=<name>`, `POST ?zone=<name>&key=<key>`, `DELETE ?zone=<name>&key=<key>`.
+
+#![allow(static_mut_refs)]
+
+use crate::NgxArgsFilterModule;
+use crate::logging::{with_config_context, with_request_context};
+use crate::response::send_body;
+use crate::status::{NgxHttpStatus, NgxStatus};
+use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK};
+use ngx::ffi::{
+    NGX_CONF_NOARGS, NGX_HTTP_DELETE, NGX_HTTP_GET, NGX_HTTP_HEAD, NGX_HTTP_LOC_CONF,
+    NGX_HTTP_POST, ngx_command_t, ngx_conf_t, ngx_http_arg, ngx_http_discard_request_body,
+    ngx_http_request_t, ngx_int_t, ngx_str_t, ngx_uint_t, ngx_unescape_uri,
+};
+use ngx::http::{HttpModuleLocationConf, HttpModuleMainConf, NgxHttpCoreModule};
+use tracing::{debug, error};
+
+#[unsafe(no_mangle)]
+pub static mut ARGS_FILTER_API_COMMAND: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("args_filter_api"),
+    type_: (NGX_HTTP_LOC_CONF | NGX_CONF_NOARGS) as _,
+    set: Some(args_filter_api_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_api_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+
+        let Some(clcf) = NgxHttpCoreModule::location_conf_mut(cf_ref) else {
+            error!("args_filter_api: core location configuration unavailable");
+            return NGX_CONF_ERROR;
+        };
+
+        clcf.handler = Some(args_filter_api_handler);
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_api_handler(r: *mut ngx_http_request_t) -> ngx_int_t {
+    if r.is_null() {
+        return NgxStatus::ERROR;
+    }
+
+    let log = unsafe {
+        let conn = (*r).connection;
+        if conn.is_null() {
+            core::ptr::null_mut()
+        } else {
+            (*conn).log
+        }
+    };
+
+    with_request_context(log, || {
+        let req = unsafe { ngx::http::Request::from_ngx_http_request(r) };
+        let method = unsafe { (*r).method };
+
+        if method & (NGX_HTTP_GET | NGX_HTTP_HEAD | NGX_HTTP_POST | NGX_HTTP_DELETE) as ngx_uint_t
+            == 0
+        {
+            return NgxHttpStatus::NOT_ALLOWED;
+        }
+
+        let rc = unsafe { ngx_http_discard_request_body(r) };
+        if rc != NgxStatus::OK {
+            return rc;
+        }
+
+        let Some(zone_name) = (unsafe { request_arg(r, b"zone") }) else {
+            return NgxHttpStatus::BAD_REQUEST;
+        };
+
+        let Some(zone) = NgxArgsFilterModule::main_conf(req)
+            .and_then(|c| c.zones.as_ref())
+            .and_then(|m| m.get(zone_name.as_slice()))
+            .copied()
+        else {
+            return NgxHttpStatus::NOT_FOUND;
+        };
+
+        let zone_text = String::from_utf8_lossy(&zone_name);
+
+        if method & (NGX_HTTP_GET | NGX_HTTP_HEAD) as ngx_uint_t != 0 {
+            let mut body = std::vec::Vec::new();
+            for key in zone.snapshot() {
+                body.extend_from_slice(&key);
+                body.push(b'\n');
+            }
+            return unsafe { send_body(r, ngx::ngx_string!("text/plain"), &body) };
+        }
+
+        let Some(key) = (unsafe { request_arg(r, b"key") }) else {
+            return NgxHttpStatus::BAD_REQUEST;
+        };
+        let key_text = String::from_utf8_lossy(&key);
+
+        if method & NGX_HTTP_POST as ngx_uint_t != 0 {
+            return match zone.insert(&key) {
+                Ok(added) => {
+                    debug!(
+                        "args_filter_api: zone={} key='{}' added={}",
+                        zone_text, key_text, added
+                    );
+                    NgxHttpStatus::NO_CONTENT
+                }
+                Err(_) => {
+                    error!(
+                        "args_filter_api: could not store key '{}' in zone {}",
+                        key_text, zone_text
+                    );
+                    NgxHttpStatus::INSUFFICIENT_STORAGE
+                }
+            };
+        }
+
+        if !zone.remove(&key) {
+            return NgxHttpStatus::NOT_FOUND;
+        }
+
+        debug!(
+            "args_filter_api: zone={} key='{}' removed",
+            zone_text, key_text
+        );
+        NgxHttpStatus::NO_CONTENT
+    })
+}
+
+/// Return the URI-decoded value of query argument `name`, if present and non-empty.
+unsafe fn request_arg(r: *mut ngx_http_request_t, name: &[u8]) -> Option<std::vec::Vec<u8>> {
+    let mut value = ngx_str_t::default();
+    if unsafe { ngx_http_arg(r, name.as_ptr().cast_mut(), name.len(), &raw mut value) }
+        != NgxStatus::OK
+    {
+        return None;
+    }
+
+    let mut decoded = vec![0u8; value.len];
+    let start = decoded.as_mut_ptr();
+    let mut dst = start;
+    let mut src = value.data;
+    unsafe { ngx_unescape_uri(&raw mut dst, &raw mut src, value.len, 0) };
+
+    let len = usize::try_from(unsafe { dst.offset_from(start) }).ok()?;
+    decoded.truncate(len);
+    (!decoded.is_empty()).then_some(decoded)
+}
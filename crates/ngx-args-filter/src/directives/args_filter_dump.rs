@@ -0,0 +1,263 @@
+//! `args_filter_dump` content handler.
+//!
+//! Serializes the `args_filter` definitions of every `http`, `server`, and
+//! `location` block as JSON so the rules actually loaded by a running nginx can
+//! be audited.
+
+#![allow(static_mut_refs)]
+
+use crate::NgxArgsFilterModule;
+use crate::config::MainConf;
+use crate::config::args_filter::{
+    ArgsFilterDef, ArgsFilterMap, EmptyValuePolicy, InitialPolicy, InvalidEscapePolicy, MatchMode,
+    ValuelessKeyPolicy, complex_value_source,
+};
+use crate::config::rename::RenameSource;
+use crate::json::{push_opt_str, push_str};
+use crate::logging::{with_config_context, with_request_context};
+use crate::nginx_str::NginxStr;
+use crate::response::send_body;
+use crate::status::{NgxHttpStatus, NgxStatus};
+use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK};
+use ngx::ffi::{
+    NGX_CONF_NOARGS, NGX_HTTP_GET, NGX_HTTP_HEAD, NGX_HTTP_LOC_CONF, ngx_command_t, ngx_conf_t,
+    ngx_http_discard_request_body, ngx_http_request_t, ngx_int_t, ngx_uint_t,
+};
+use ngx::http::{HttpModuleLocationConf, HttpModuleMainConf, NgxHttpCoreModule};
+use std::fmt::Write;
+use tracing::error;
+
+#[unsafe(no_mangle)]
+pub static mut ARGS_FILTER_DUMP_COMMAND: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("args_filter_dump"),
+    type_: (NGX_HTTP_LOC_CONF | NGX_CONF_NOARGS) as _,
+    set: Some(args_filter_dump_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_dump_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+
+        let Some(clcf) = NgxHttpCoreModule::location_conf_mut(cf_ref) else {
+            error!("args_filter_dump: core location configuration unavailable");
+            return NGX_CONF_ERROR;
+        };
+
+        clcf.handler = Some(args_filter_dump_handler);
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_dump_handler(r: *mut ngx_http_request_t) -> ngx_int_t {
+    if r.is_null() {
+        return NgxStatus::ERROR;
+    }
+
+    let log = unsafe {
+        let conn = (*r).connection;
+        if conn.is_null() {
+            core::ptr::null_mut()
+        } else {
+            (*conn).log
+        }
+    };
+
+    with_request_context(log, || {
+        let req = unsafe { ngx::http::Request::from_ngx_http_request(r) };
+
+        if unsafe { (*r).method } & (NGX_HTTP_GET | NGX_HTTP_HEAD) as ngx_uint_t == 0 {
+            return NgxHttpStatus::NOT_ALLOWED;
+        }
+
+        let rc = unsafe { ngx_http_discard_request_body(r) };
+        if rc != NgxStatus::OK {
+            return rc;
+        }
+
+        let Some(main_conf) = NgxArgsFilterModule::main_conf(req) else {
+            error!("args_filter_dump: main configuration unavailable");
+            return NgxStatus::ERROR;
+        };
+
+        let body = dump_filters(main_conf);
+        unsafe { send_body(r, ngx::ngx_string!("application/json"), body.as_bytes()) }
+    })
+}
+
+/// Render `{"args_filters": [...]}`: `http`-level filters first, then each
+/// `server` and `location` block in configuration order, with the filters of
+/// a block sorted by variable name.
+fn dump_filters(main_conf: &MainConf) -> String {
+    let mut out = String::from(r#"{"args_filters":["#);
+    if let Some(filters) = main_conf.args_filters.as_ref() {
+        dump_scope(&mut out, "http", filters);
+    }
+    for scoped in main_conf.scoped_filters.iter().flatten() {
+        // SAFETY: filter maps live in module configurations allocated from the
+        // cycle pool, which outlives every request.
+        let filters = unsafe { scoped.filters.as_ref() };
+        dump_scope(&mut out, &scoped.scope.to_string(), filters);
+    }
+    out.push_str("]}\n");
+    out
+}
+
+fn dump_scope(out: &mut String, scope: &str, filters: &ArgsFilterMap) {
+    let mut filters: Vec<_> = filters.iter().collect();
+    filters.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (name, filter) in filters {
+        if !out.ends_with('[') {
+            out.push(',');
+        }
+        dump_filter(out, scope, name.as_bytes(), filter);
+    }
+}
+
+fn dump_filter(out: &mut String, scope: &str, name: &[u8], filter: &ArgsFilterDef) {
+    out.push_str(r#"{"name":"#);
+    push_str(out, name);
+    out.push_str(r#","scope":"#);
+    push_str(out, scope.as_bytes());
+
+    let initial = match filter.initial {
+        InitialPolicy::All => "all",
+        InitialPolicy::None => "none",
+    };
+    let match_mode = match filter.match_mode {
+        MatchMode::First => "first",
+        MatchMode::Last => "last",
+    };
+    let invalid_escapes = match filter.invalid_escapes {
+        InvalidEscapePolicy::Literal => "literal",
+        InvalidEscapePolicy::Drop => "drop",
+        InvalidEscapePolicy::Keep => "keep",
+    };
+    let empty_values = match filter.empty_values {
+        EmptyValuePolicy::Keep => "keep",
+        EmptyValuePolicy::Drop => "drop",
+    };
+    let valueless_keys = match filter.valueless_keys {
+        ValuelessKeyPolicy::Keep => "keep",
+        ValuelessKeyPolicy::Drop => "drop",
+        ValuelessKeyPolicy::AsEmpty => "as_empty",
+    };
+    let _ = write!(
+        out,
+        r#","initial":"{initial}","match":"{match_mode}","decode_keys":{},"invalid_escapes":"{invalid_escapes}","array_keys":{},"path_separator":"{}","empty_values":"{empty_values}","valueless_keys":"{valueless_keys}","regex_match_limit":{},"regex_depth_limit":{},"regex_limit_exceeded":"{}","volatile":{},"extends":"#,
+        filter.decode_keys,
+        filter.array_keys,
+        filter.path_separator.name(),
+        filter.regex_limits.match_limit,
+        filter.regex_limits.depth_limit,
+        filter.regex_limits.exceeded.name(),
+        filter.volatile
+    );
+    push_opt_str(out, filter.extends.as_ref().map(NginxStr::as_bytes));
+
+    out.push_str(r#","rules":["#);
+    for (idx, rule) in filter.rules.iter().flatten().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+
+        let _ = write!(
+            out,
+            r#"{{"action":"{}","kind":"{}","pattern":"#,
+            rule.action.directive_name(),
+            rule.matcher.kind()
+        );
+        push_str(out, rule.matcher.source().as_bytes());
+        let _ = write!(
+            out,
+            r#","case_insensitive":{},"condition":"#,
+            rule.matcher.case_insensitive()
+        );
+        push_opt_str(out, rule.condition_source().as_ref().map(String::as_bytes));
+
+        out.push_str(r#","value":"#);
+        if let Some(value) = rule.value.as_ref() {
+            out.push_str(r#"{"pattern":"#);
+            push_str(out, value.pattern.as_bytes());
+            let _ = write!(out, r#","case_insensitive":{}}}"#, value.case_insensitive);
+        } else {
+            out.push_str("null");
+        }
+        out.push('}');
+    }
+
+    out.push_str(r#"],"validations":["#);
+    for (idx, validation) in filter.validations.iter().flatten().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+
+        out.push_str(r#"{"key":"#);
+        push_str(out, validation.key.as_bytes());
+        out.push_str(r#","type":"#);
+        push_str(out, validation.value_type.to_string().as_bytes());
+        out.push_str(r#","default":"#);
+        push_opt_str(out, validation.default.as_ref().map(NginxStr::as_bytes));
+        out.push('}');
+    }
+
+    out.push_str(r#"],"max_value_lengths":["#);
+    for (idx, limit) in filter.value_length_limits.iter().flatten().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+
+        out.push_str(r#"{"key":"#);
+        push_opt_str(out, limit.key.as_ref().map(NginxStr::as_bytes));
+        let _ = write!(
+            out,
+            r#","length":{},"action":"{}"}}"#,
+            limit.max,
+            limit.policy.name()
+        );
+    }
+
+    out.push_str(r#"],"renames":["#);
+    for (idx, rename) in filter.renames.iter().flatten().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+
+        let (kind, from, case_insensitive) = match &rename.source {
+            RenameSource::Literal(from) => ("literal", from, false),
+            RenameSource::Regex(regex) => ("regex", &regex.pattern, regex.case_insensitive),
+        };
+        let _ = write!(out, r#"{{"kind":"{kind}","from":"#);
+        push_str(out, from.as_bytes());
+        let _ = write!(out, r#","case_insensitive":{case_insensitive},"to":"#);
+        push_str(out, rename.to.as_bytes());
+        out.push('}');
+    }
+
+    out.push_str(r#"],"injected_args":["#);
+    for (idx, arg) in filter.injected_args.iter().flatten().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+
+        let _ = write!(
+            out,
+            r#"{{"action":"{}","key":"#,
+            arg.action.directive_name()
+        );
+        push_str(out, arg.key.as_bytes());
+        out.push_str(r#","value":"#);
+        push_str(out, complex_value_source(arg.value).as_bytes());
+        out.push('}');
+    }
+    out.push_str("]}");
+}
@@ -0,0 +1,169 @@
+//! `args_filter_keys` block directive implementation.
+//!
+//! Declares a named list of keys that `include @name;` and `exclude @name;`
+//! expand inside `args_filter {}` blocks.
+
+#![allow(static_mut_refs)]
+
+use crate::NgxArgsFilterModule;
+use crate::conf_ext::NgxConfExt;
+use crate::config::MainConf;
+use crate::config::args_filter::{RuleMatcher, compile_operator_matcher, compile_pattern_matcher};
+use crate::config::path::PathSeparator;
+use crate::logging::with_config_context;
+use crate::nginx_str::NginxStr;
+use ngx::collections::Vec;
+use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK, Pool};
+use ngx::ffi::{NGX_CONF_BLOCK, NGX_CONF_TAKE1, NGX_HTTP_MAIN_CONF, ngx_command_t, ngx_conf_t};
+use ngx::http::HttpModuleMainConf;
+use tracing::error;
+
+#[unsafe(no_mangle)]
+pub static mut ARGS_FILTER_KEYS_COMMAND: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("args_filter_keys"),
+    type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_BLOCK | NGX_CONF_TAKE1) as _,
+    set: Some(args_filter_keys_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_keys_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let main_conf = unsafe { conf.cast::<MainConf>().as_mut().expect("main_conf") };
+        let args = cf_ref.args();
+
+        let raw_name = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        if validate_key_list_name(raw_name).is_err() {
+            return NGX_CONF_ERROR;
+        }
+
+        let Ok(list_name) = NginxStr::from_bytes(cf_ref.pool(), raw_name) else {
+            error!("failed to allocate args_filter_keys list name");
+            return NGX_CONF_ERROR;
+        };
+
+        if main_conf.key_lists.is_none() {
+            let Ok(map) = ngx::collections::RbTreeMap::try_new_in(cf_ref.pool()) else {
+                error!("failed to initialize args_filter_keys map");
+                return NGX_CONF_ERROR;
+            };
+            main_conf.key_lists = Some(map);
+        }
+
+        if main_conf
+            .key_lists
+            .as_ref()
+            .is_some_and(|m| m.get(list_name.as_bytes()).is_some())
+        {
+            error!("duplicate args_filter_keys declaration for @{}", list_name);
+            return NGX_CONF_ERROR;
+        }
+
+        let mut matchers: Vec<RuleMatcher, Pool> = Vec::new_in(cf_ref.pool());
+
+        let mut block_cf = *cf_ref;
+        block_cf.handler = Some(args_filter_keys_block_handler);
+        block_cf.handler_conf = core::ptr::addr_of_mut!(matchers).cast();
+
+        let rv = unsafe { ngx::ffi::ngx_conf_parse(&raw mut block_cf, core::ptr::null_mut()) };
+        if rv != NGX_CONF_OK {
+            return rv;
+        }
+
+        let Some(lists) = main_conf.key_lists.as_mut() else {
+            error!("args_filter_keys map unavailable after parse");
+            return NGX_CONF_ERROR;
+        };
+
+        if lists.try_insert(list_name, matchers).is_err() {
+            error!("failed to store args_filter_keys list");
+            return NGX_CONF_ERROR;
+        }
+
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_keys_block_handler(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let matchers = unsafe { &mut *cf_ref.handler_conf.cast::<Vec<RuleMatcher, Pool>>() };
+
+        let matcher = match args.len() {
+            1 => match compile_pattern_matcher(cf_ref, &args[0], PathSeparator::Both) {
+                Ok(Some(matcher)) => matcher,
+                Ok(None) => {
+                    let Ok(key) = NginxStr::from_ngx_str(cf_ref, &args[0]) else {
+                        error!("failed to allocate args_filter_keys entry");
+                        return NGX_CONF_ERROR;
+                    };
+                    RuleMatcher::Literal(key)
+                }
+                Err(()) => return NGX_CONF_ERROR,
+            },
+            2 => match compile_operator_matcher(cf, args[0], args[1]) {
+                Ok(Some(matcher)) => matcher,
+                Ok(None) => {
+                    error!(r#""args_filter_keys" entry expects literal, "=*", "~", or "~*""#);
+                    return NGX_CONF_ERROR;
+                }
+                Err(()) => return NGX_CONF_ERROR,
+            },
+            _ => {
+                error!(r#"invalid number of arguments in "args_filter_keys" entry"#);
+                return NGX_CONF_ERROR;
+            }
+        };
+
+        matchers.push(matcher);
+        NGX_CONF_OK
+    })
+}
+
+/// Resolve an `@name` reference to the matchers of a declared `args_filter_keys` list.
+pub fn find_key_list(cf: &ngx_conf_t, reference: &[u8]) -> Result<&'static [RuleMatcher], ()> {
+    let Some(name) = reference.strip_prefix(b"@") else {
+        error!("args_filter_keys reference must start with '@'");
+        return Err(());
+    };
+
+    validate_key_list_name(name)?;
+
+    NgxArgsFilterModule::main_conf(cf)
+        .and_then(|c| c.key_lists.as_ref())
+        .and_then(|m| m.get(name))
+        .map(Vec::as_slice)
+        .ok_or_else(|| {
+            error!(
+                "unknown args_filter_keys list @{}",
+                String::from_utf8_lossy(name)
+            );
+        })
+}
+
+fn validate_key_list_name(name: &[u8]) -> Result<(), ()> {
+    if name.is_empty() {
+        error!("args_filter_keys list name cannot be empty");
+        return Err(());
+    }
+
+    if !name.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+        error!("args_filter_keys list name contains invalid characters");
+        return Err(());
+    }
+
+    Ok(())
+}
@@ -1,27 +1,82 @@
 //! Nested directives for `args_filter {}` blocks.
 //!
-//! Supported directives: `initial`, `include`, `exclude`, and `volatile`.
+//! Supported directives: `extends`, `initial`, `match`, `decode_keys`, `array_keys`,
+//! `path_separator`, `empty_values`, `valueless_keys`, `regex_match_limit`,
+//! `regex_depth_limit`, `regex_limit_exceeded`, `include`, `exclude`,
+//! `exclude_value`, `rules_file`, `validate`, `max_value_length`, `rename`, `set`,
+//! `add`, and `volatile`.
 
 #![allow(static_mut_refs)]
 
 use crate::conf_ext::NgxConfExt;
-use crate::config::args_filter::{ArgsFilterDef, InitialPolicy};
+use crate::config::args_filter::{
+    ArgsFilterDef, CompiledComplexValue, CompiledRegex, EmptyValuePolicy, InitialPolicy,
+    InvalidEscapePolicy, MatchMode, RuleAction, RuleMatcher, ValuelessKeyPolicy,
+    compile_complex_value, compile_operator_matcher, compile_pattern_matcher, compile_regex,
+    has_variables,
+};
+use crate::config::inject::{InjectAction, InjectedArg};
+use crate::config::path::PathSeparator;
+use crate::config::regex_limits::RegexLimitPolicy;
+use crate::config::rename::{Rename, RenameSource, check_replacement};
+use crate::config::rules_file::split_rule_line;
+use crate::config::validate::{Validation, ValueType};
+use crate::config::value_length::{LengthPolicy, ValueLengthLimit};
 use crate::directives::NGX_EMPTY_COMMAND;
+use crate::directives::args_filter::{escape_component, find_declared_filter, parse_variable_name};
+use crate::directives::args_filter_keys::find_key_list;
+use crate::directives::args_filter_zone::{find_zone, is_zone_reference};
 use crate::logging::with_config_context;
 use crate::nginx_str::NginxStr;
+use crate::status::NgxStatus;
 use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK};
-use ngx::ffi::{NGX_CONF_NOARGS, NGX_CONF_TAKE1, NGX_CONF_TAKE2, ngx_command_t, ngx_conf_t};
+use ngx::ffi::{
+    NGX_CONF_1MORE, NGX_CONF_NOARGS, NGX_CONF_TAKE1, NGX_CONF_TAKE2, NGX_CONF_TAKE3,
+    ngx_array_push, ngx_atoi, ngx_close_glob, ngx_command_t, ngx_conf_file_t, ngx_conf_full_name,
+    ngx_conf_t, ngx_glob_t, ngx_open_glob, ngx_pstrdup, ngx_read_glob, ngx_str_t,
+};
+use std::ffi::OsStr;
+use std::io::Read;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
 use tracing::error;
 
 #[unsafe(no_mangle)]
-pub static mut ARGS_FILTER_NESTED_COMMANDS: [ngx_command_t; 5] = [
+pub static mut ARGS_FILTER_NESTED_COMMANDS: [ngx_command_t; 22] = [
+    unsafe { ARGS_FILTER_EXTENDS_COMMAND_NESTED },
     unsafe { ARGS_FILTER_INITIAL_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_MATCH_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_DECODE_KEYS_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_ARRAY_KEYS_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_PATH_SEPARATOR_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_EMPTY_VALUES_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_VALUELESS_KEYS_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_REGEX_MATCH_LIMIT_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_REGEX_DEPTH_LIMIT_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_REGEX_LIMIT_EXCEEDED_COMMAND_NESTED },
     unsafe { ARGS_FILTER_EXCLUDE_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_EXCLUDE_VALUE_COMMAND_NESTED },
     unsafe { ARGS_FILTER_INCLUDE_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_RULES_FILE_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_VALIDATE_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_MAX_VALUE_LENGTH_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_RENAME_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_SET_COMMAND_NESTED },
+    unsafe { ARGS_FILTER_ADD_COMMAND_NESTED },
     unsafe { ARGS_FILTER_VOLATILE_COMMAND_NESTED },
     NGX_EMPTY_COMMAND,
 ];
 
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_EXTENDS_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("extends"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_extends_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
 #[unsafe(no_mangle)]
 static mut ARGS_FILTER_INITIAL_COMMAND_NESTED: ngx_command_t = ngx_command_t {
     name: ngx::ngx_string!("initial"),
@@ -32,26 +87,186 @@ static mut ARGS_FILTER_INITIAL_COMMAND_NESTED: ngx_command_t = ngx_command_t {
     post: core::ptr::null_mut(),
 };
 
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_MATCH_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("match"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_match_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_DECODE_KEYS_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("decode_keys"),
+    type_: (NGX_CONF_TAKE1 | NGX_CONF_TAKE2) as _,
+    set: Some(args_filter_decode_keys_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_ARRAY_KEYS_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("array_keys"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_array_keys_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_PATH_SEPARATOR_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("path_separator"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_path_separator_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_EMPTY_VALUES_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("empty_values"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_empty_values_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_VALUELESS_KEYS_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("valueless_keys"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_valueless_keys_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_REGEX_MATCH_LIMIT_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("regex_match_limit"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_regex_match_limit_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_REGEX_DEPTH_LIMIT_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("regex_depth_limit"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_regex_depth_limit_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_REGEX_LIMIT_EXCEEDED_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("regex_limit_exceeded"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_regex_limit_exceeded_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
 #[unsafe(no_mangle)]
 static mut ARGS_FILTER_EXCLUDE_COMMAND_NESTED: ngx_command_t = ngx_command_t {
     name: ngx::ngx_string!("exclude"),
-    type_: (NGX_CONF_TAKE1 | NGX_CONF_TAKE2) as _,
+    type_: NGX_CONF_1MORE as _,
     set: Some(args_filter_exclude_set),
     conf: 0,
     offset: 0,
     post: core::ptr::null_mut(),
 };
 
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_EXCLUDE_VALUE_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("exclude_value"),
+    type_: (NGX_CONF_TAKE2 | NGX_CONF_TAKE3) as _,
+    set: Some(args_filter_exclude_value_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
 #[unsafe(no_mangle)]
 static mut ARGS_FILTER_INCLUDE_COMMAND_NESTED: ngx_command_t = ngx_command_t {
     name: ngx::ngx_string!("include"),
-    type_: (NGX_CONF_TAKE1 | NGX_CONF_TAKE2) as _,
+    type_: NGX_CONF_1MORE as _,
     set: Some(args_filter_include_set),
     conf: 0,
     offset: 0,
     post: core::ptr::null_mut(),
 };
 
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_RULES_FILE_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("rules_file"),
+    type_: NGX_CONF_TAKE1 as _,
+    set: Some(args_filter_rules_file_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_VALIDATE_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("validate"),
+    type_: (NGX_CONF_TAKE2 | NGX_CONF_TAKE3) as _,
+    set: Some(args_filter_validate_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_MAX_VALUE_LENGTH_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("max_value_length"),
+    type_: (NGX_CONF_TAKE1 | NGX_CONF_TAKE2 | NGX_CONF_TAKE3) as _,
+    set: Some(args_filter_max_value_length_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_RENAME_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("rename"),
+    type_: (NGX_CONF_TAKE2 | NGX_CONF_TAKE3) as _,
+    set: Some(args_filter_rename_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_SET_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("set"),
+    type_: NGX_CONF_TAKE2 as _,
+    set: Some(args_filter_set_arg_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+static mut ARGS_FILTER_ADD_COMMAND_NESTED: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("add"),
+    type_: NGX_CONF_TAKE2 as _,
+    set: Some(args_filter_add_arg_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
 #[unsafe(no_mangle)]
 static mut ARGS_FILTER_VOLATILE_COMMAND_NESTED: ngx_command_t = ngx_command_t {
     name: ngx::ngx_string!("volatile"),
@@ -62,10 +277,79 @@ static mut ARGS_FILTER_VOLATILE_COMMAND_NESTED: ngx_command_t = ngx_command_t {
     post: core::ptr::null_mut(),
 };
 
+fn is_key_list_reference(arg: &ngx_str_t) -> bool {
+    arg.len > 0 && unsafe { *arg.data } == b'@'
+}
+
 unsafe fn get_current_filter(cf: *mut ngx_conf_t) -> *mut ArgsFilterDef {
     unsafe { (*cf).handler_conf.cast::<ArgsFilterDef>() }
 }
 
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_extends_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        if args.len() != 2 {
+            error!(r#"invalid number of arguments in "extends" directive"#);
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.extends.is_some() {
+            error!(r#""extends" directive is duplicate"#);
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.initial_set
+            || filter.match_set
+            || filter.decode_keys_set
+            || filter.array_keys_set
+            || filter.path_separator_set
+            || filter.empty_values_set
+            || filter.valueless_keys_set
+            || filter.regex_match_limit_set
+            || filter.regex_depth_limit_set
+            || filter.regex_limit_exceeded_set
+            || filter.validations.is_some()
+            || filter.value_length_limits.is_some()
+            || filter.renames.is_some()
+            || filter.injected_args.is_some()
+            || filter.rules.is_some()
+            || filter.volatile
+        {
+            error!(r#""extends" must be the first directive in the block"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let Ok(base_name) = parse_variable_name(cf_ref, &args[1]) else {
+            return NGX_CONF_ERROR;
+        };
+
+        // Only filters declared earlier are visible here, so a chain of
+        // `extends` can never loop back onto the filter being defined.
+        let Some(base) = find_declared_filter(cf_ref, base_name.as_bytes()) else {
+            error!(
+                r#""extends" references undeclared args_filter ${}"#,
+                base_name
+            );
+            return NGX_CONF_ERROR;
+        };
+
+        if filter.extend_from(cf_ref.pool(), base_name, base).is_err() {
+            error!("failed to copy args_filter rules for extends");
+            return NGX_CONF_ERROR;
+        }
+
+        NGX_CONF_OK
+    })
+}
+
 #[unsafe(no_mangle)]
 extern "C" fn args_filter_initial_set(
     cf: *mut ngx_conf_t,
@@ -103,7 +387,7 @@ extern "C" fn args_filter_initial_set(
 }
 
 #[unsafe(no_mangle)]
-extern "C" fn args_filter_include_set(
+extern "C" fn args_filter_match_set(
     cf: *mut ngx_conf_t,
     _cmd: *mut ngx_command_t,
     _conf: *mut core::ffi::c_void,
@@ -113,44 +397,84 @@ extern "C" fn args_filter_include_set(
         let args = cf_ref.args();
         let filter = unsafe { &mut *get_current_filter(cf) };
 
-        if args.len() != 2 && args.len() != 3 {
-            error!(r#"invalid number of arguments in "include" directive"#);
+        if args.len() != 2 {
+            error!(r#"invalid number of arguments in "match" directive"#);
             return NGX_CONF_ERROR;
         }
 
-        if args.len() == 2 {
-            let Ok(key) = NginxStr::from_ngx_str(cf_ref, &args[1]) else {
-                error!("failed to allocate include key");
-                return NGX_CONF_ERROR;
-            };
+        if filter.match_set {
+            error!(r#""match" directive is duplicate"#);
+            return NGX_CONF_ERROR;
+        }
 
-            filter.add_include_literal(cf_ref.pool(), key);
-            return NGX_CONF_OK;
+        let value = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        filter.match_mode = if value == b"first" {
+            MatchMode::First
+        } else if value == b"last" {
+            MatchMode::Last
+        } else {
+            error!(r#""match" must be "first" or "last""#);
+            return NGX_CONF_ERROR;
+        };
+
+        filter.match_set = true;
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_decode_keys_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        if args.len() != 2 && args.len() != 3 {
+            error!(r#"invalid number of arguments in "decode_keys" directive"#);
+            return NGX_CONF_ERROR;
         }
 
-        let mode = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
-        let case_insensitive = if mode == b"~" {
-            false
-        } else if mode == b"~*" {
+        if filter.decode_keys_set {
+            error!(r#""decode_keys" directive is duplicate"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let value = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        filter.decode_keys = if value == b"on" {
             true
+        } else if value == b"off" {
+            false
         } else {
-            error!(r#""include" expects literal, "~", or "~*""#);
+            error!(r#""decode_keys" must be "on" or "off""#);
             return NGX_CONF_ERROR;
         };
 
-        if filter
-            .add_include_regex(cf, args[2], case_insensitive)
-            .is_err()
-        {
-            return NGX_CONF_ERROR;
+        if let Some(param) = args.get(2) {
+            let param = unsafe { std::slice::from_raw_parts(param.data, param.len) };
+            filter.invalid_escapes = match param {
+                b"invalid=literal" => InvalidEscapePolicy::Literal,
+                b"invalid=drop" => InvalidEscapePolicy::Drop,
+                b"invalid=keep" => InvalidEscapePolicy::Keep,
+                _ => {
+                    error!(
+                        r#""decode_keys" expects "invalid=literal", "invalid=drop", or "invalid=keep""#
+                    );
+                    return NGX_CONF_ERROR;
+                }
+            };
         }
 
+        filter.decode_keys_set = true;
         NGX_CONF_OK
     })
 }
 
 #[unsafe(no_mangle)]
-extern "C" fn args_filter_exclude_set(
+extern "C" fn args_filter_array_keys_set(
     cf: *mut ngx_conf_t,
     _cmd: *mut ngx_command_t,
     _conf: *mut core::ffi::c_void,
@@ -160,44 +484,85 @@ extern "C" fn args_filter_exclude_set(
         let args = cf_ref.args();
         let filter = unsafe { &mut *get_current_filter(cf) };
 
-        if args.len() != 2 && args.len() != 3 {
-            error!(r#"invalid number of arguments in "exclude" directive"#);
+        if args.len() != 2 {
+            error!(r#"invalid number of arguments in "array_keys" directive"#);
             return NGX_CONF_ERROR;
         }
 
-        if args.len() == 2 {
-            let Ok(key) = NginxStr::from_ngx_str(cf_ref, &args[1]) else {
-                error!("failed to allocate exclude key");
-                return NGX_CONF_ERROR;
-            };
-
-            filter.add_exclude_literal(cf_ref.pool(), key);
-            return NGX_CONF_OK;
+        if filter.array_keys_set {
+            error!(r#""array_keys" directive is duplicate"#);
+            return NGX_CONF_ERROR;
         }
 
-        let mode = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
-        let case_insensitive = if mode == b"~" {
-            false
-        } else if mode == b"~*" {
+        let value = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        filter.array_keys = if value == b"on" {
             true
+        } else if value == b"off" {
+            false
         } else {
-            error!(r#""exclude" expects literal, "~", or "~*""#);
+            error!(r#""array_keys" must be "on" or "off""#);
             return NGX_CONF_ERROR;
         };
 
+        if filter.array_keys && filter.has_path_rules() {
+            error!(r#""path:" rules cannot be used with "array_keys on""#);
+            return NGX_CONF_ERROR;
+        }
+
+        filter.array_keys_set = true;
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_path_separator_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        if args.len() != 2 {
+            error!(r#"invalid number of arguments in "path_separator" directive"#);
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.path_separator_set {
+            error!(r#""path_separator" directive is duplicate"#);
+            return NGX_CONF_ERROR;
+        }
+
+        // `path:` rules capture the separator when they are compiled.
         if filter
-            .add_exclude_regex(cf, args[2], case_insensitive)
-            .is_err()
+            .rules
+            .as_ref()
+            .is_some_and(|rules| rules.len() > filter.inherited_rules)
         {
+            error!(r#""path_separator" must precede "include" and "exclude""#);
             return NGX_CONF_ERROR;
         }
 
+        let value = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        filter.path_separator = match value {
+            b"brackets" => PathSeparator::Brackets,
+            b"dot" => PathSeparator::Dot,
+            b"both" => PathSeparator::Both,
+            _ => {
+                error!(r#""path_separator" must be "brackets", "dot", or "both""#);
+                return NGX_CONF_ERROR;
+            }
+        };
+
+        filter.path_separator_set = true;
         NGX_CONF_OK
     })
 }
 
 #[unsafe(no_mangle)]
-extern "C" fn args_filter_volatile_set(
+extern "C" fn args_filter_empty_values_set(
     cf: *mut ngx_conf_t,
     _cmd: *mut ngx_command_t,
     _conf: *mut core::ffi::c_void,
@@ -207,12 +572,907 @@ extern "C" fn args_filter_volatile_set(
         let args = cf_ref.args();
         let filter = unsafe { &mut *get_current_filter(cf) };
 
-        if args.len() != 1 {
-            error!(r#"invalid number of arguments in "volatile" directive"#);
+        if args.len() != 2 {
+            error!(r#"invalid number of arguments in "empty_values" directive"#);
             return NGX_CONF_ERROR;
         }
 
-        filter.volatile = true;
+        if filter.empty_values_set {
+            error!(r#""empty_values" directive is duplicate"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let value = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        filter.empty_values = match value {
+            b"keep" => EmptyValuePolicy::Keep,
+            b"drop" => EmptyValuePolicy::Drop,
+            _ => {
+                error!(r#""empty_values" must be "keep" or "drop""#);
+                return NGX_CONF_ERROR;
+            }
+        };
+
+        filter.empty_values_set = true;
         NGX_CONF_OK
     })
 }
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_valueless_keys_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        if args.len() != 2 {
+            error!(r#"invalid number of arguments in "valueless_keys" directive"#);
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.valueless_keys_set {
+            error!(r#""valueless_keys" directive is duplicate"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let value = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        filter.valueless_keys = match value {
+            b"keep" => ValuelessKeyPolicy::Keep,
+            b"drop" => ValuelessKeyPolicy::Drop,
+            b"as_empty" => ValuelessKeyPolicy::AsEmpty,
+            _ => {
+                error!(r#""valueless_keys" must be "keep", "drop", or "as_empty""#);
+                return NGX_CONF_ERROR;
+            }
+        };
+
+        filter.valueless_keys_set = true;
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_regex_match_limit_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        let Ok(limit) = parse_regex_limit(cf, "regex_match_limit", filter.regex_match_limit_set)
+        else {
+            return NGX_CONF_ERROR;
+        };
+
+        filter.regex_limits.match_limit = limit;
+        filter.regex_match_limit_set = true;
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_regex_depth_limit_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        let Ok(limit) = parse_regex_limit(cf, "regex_depth_limit", filter.regex_depth_limit_set)
+        else {
+            return NGX_CONF_ERROR;
+        };
+
+        filter.regex_limits.depth_limit = limit;
+        filter.regex_depth_limit_set = true;
+        NGX_CONF_OK
+    })
+}
+
+/// Parse the positive integer argument of `regex_match_limit` or `regex_depth_limit`.
+fn parse_regex_limit(cf: *mut ngx_conf_t, name: &str, duplicate: bool) -> Result<u32, ()> {
+    let cf_ref = unsafe { cf.as_mut().expect("cf") };
+    let args = cf_ref.args();
+
+    if args.len() != 2 {
+        error!(r#"invalid number of arguments in "{}" directive"#, name);
+        return Err(());
+    }
+
+    if duplicate {
+        error!(r#""{}" directive is duplicate"#, name);
+        return Err(());
+    }
+
+    let value = unsafe { ngx_atoi(args[1].data, args[1].len) };
+    match u32::try_from(value) {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => {
+            error!(r#""{}" must be a positive integer"#, name);
+            Err(())
+        }
+    }
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_regex_limit_exceeded_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        if args.len() != 2 {
+            error!(r#"invalid number of arguments in "regex_limit_exceeded" directive"#);
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.regex_limit_exceeded_set {
+            error!(r#""regex_limit_exceeded" directive is duplicate"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let value = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        filter.regex_limits.exceeded = match value {
+            b"keep" => RegexLimitPolicy::Keep,
+            b"drop" => RegexLimitPolicy::Drop,
+            b"fail" => RegexLimitPolicy::Fail,
+            _ => {
+                error!(r#""regex_limit_exceeded" must be "keep", "drop", or "fail""#);
+                return NGX_CONF_ERROR;
+            }
+        };
+
+        filter.regex_limit_exceeded_set = true;
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_include_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || add_rule_directive(cf, RuleAction::Include))
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_exclude_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || add_rule_directive(cf, RuleAction::Exclude))
+}
+
+/// `exclude_value ~ <regex>` / `exclude_value ~* <regex>`: drop any argument
+/// whose percent-decoded value matches, whatever its key.
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_exclude_value_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        let Ok((operands, condition)) = split_condition(cf, &args[1..]) else {
+            return NGX_CONF_ERROR;
+        };
+
+        let [operator, pattern] = operands else {
+            error!(r#"invalid number of arguments in "exclude_value" directive"#);
+            return NGX_CONF_ERROR;
+        };
+
+        let case_insensitive =
+            match unsafe { std::slice::from_raw_parts(operator.data, operator.len) } {
+                b"~" => false,
+                b"~*" => true,
+                _ => {
+                    error!(r#""exclude_value" expects "~" or "~*""#);
+                    return NGX_CONF_ERROR;
+                }
+            };
+
+        let Ok(regex) = compile_regex(cf, *pattern, case_insensitive) else {
+            return NGX_CONF_ERROR;
+        };
+
+        filter.add_rule(
+            cf_ref.pool(),
+            RuleAction::Exclude,
+            RuleMatcher::Any,
+            Some(regex),
+            condition,
+        );
+        NGX_CONF_OK
+    })
+}
+
+/// Shared parser for `include`/`exclude`:
+/// `<literal> | @<list> | @zone:<zone> | ~ <regex> | ~* <regex>`, optionally
+/// followed by `value~ <regex>` or `value~* <regex>`, then `if=<value>`.
+fn add_rule_directive(cf: *mut ngx_conf_t, action: RuleAction) -> *mut core::ffi::c_char {
+    let cf_ref = unsafe { cf.as_mut().expect("cf") };
+    let args = cf_ref.args();
+    let filter = unsafe { &mut *get_current_filter(cf) };
+    let directive = action.directive_name();
+
+    let Ok((operands, condition)) = split_condition(cf, &args[1..]) else {
+        return NGX_CONF_ERROR;
+    };
+
+    let Ok((operands, value)) = split_value(cf, operands) else {
+        return NGX_CONF_ERROR;
+    };
+
+    if operands.len() != 1 && operands.len() != 2 {
+        error!(
+            r#"invalid number of arguments in "{}" directive"#,
+            directive
+        );
+        return NGX_CONF_ERROR;
+    }
+
+    if operands.len() == 1 {
+        let operand = operands[0];
+
+        if is_key_list_reference(&operand) {
+            let value = unsafe { std::slice::from_raw_parts(operand.data, operand.len) };
+
+            if is_zone_reference(value) {
+                let Ok(zone) = find_zone(cf_ref, value) else {
+                    return NGX_CONF_ERROR;
+                };
+
+                filter.add_rule(
+                    cf_ref.pool(),
+                    action,
+                    RuleMatcher::Zone(zone),
+                    value,
+                    condition,
+                );
+                return NGX_CONF_OK;
+            }
+
+            let Ok(matchers) = find_key_list(cf_ref, value) else {
+                return NGX_CONF_ERROR;
+            };
+
+            if filter.array_keys
+                && matchers
+                    .iter()
+                    .any(|matcher| matches!(matcher, RuleMatcher::Path(_)))
+            {
+                error!(r#""path:" rules cannot be used with "array_keys on""#);
+                return NGX_CONF_ERROR;
+            }
+
+            filter.add_key_list(cf_ref.pool(), action, matchers, value.as_ref(), condition);
+            return NGX_CONF_OK;
+        }
+
+        let matcher = match compile_pattern_matcher(cf_ref, &operand, filter.path_separator) {
+            Ok(Some(matcher)) => matcher,
+            Err(()) => return NGX_CONF_ERROR,
+            Ok(None) if has_variables(&operand) => {
+                let Ok(cv) = compile_complex_value(cf, operand) else {
+                    return NGX_CONF_ERROR;
+                };
+                RuleMatcher::Complex(cv)
+            }
+            Ok(None) => {
+                let Ok(key) = NginxStr::from_ngx_str(cf_ref, &operand) else {
+                    error!("failed to allocate {} key", directive);
+                    return NGX_CONF_ERROR;
+                };
+                RuleMatcher::Literal(key)
+            }
+        };
+
+        if filter.array_keys && matches!(matcher, RuleMatcher::Path(_)) {
+            error!(r#""path:" rules cannot be used with "array_keys on""#);
+            return NGX_CONF_ERROR;
+        }
+
+        filter.add_rule(cf_ref.pool(), action, matcher, value, condition);
+        return NGX_CONF_OK;
+    }
+
+    let matcher = match compile_operator_matcher(cf, operands[0], operands[1]) {
+        Ok(Some(matcher)) => matcher,
+        Ok(None) => {
+            error!(r#""{}" expects literal, "=*", "~", or "~*""#, directive);
+            return NGX_CONF_ERROR;
+        }
+        Err(()) => return NGX_CONF_ERROR,
+    };
+
+    filter.add_rule(cf_ref.pool(), action, matcher, value, condition);
+    NGX_CONF_OK
+}
+
+/// Split a trailing `value~ <regex>` or `value~* <regex>` pair off rule operands.
+fn split_value(
+    cf: *mut ngx_conf_t,
+    operands: &[ngx_str_t],
+) -> Result<(&[ngx_str_t], Option<CompiledRegex>), ()> {
+    let [rest @ .., operator, pattern] = operands else {
+        return Ok((operands, None));
+    };
+
+    if rest.is_empty() {
+        return Ok((operands, None));
+    }
+
+    let case_insensitive = match unsafe { std::slice::from_raw_parts(operator.data, operator.len) }
+    {
+        b"value~" => false,
+        b"value~*" => true,
+        _ => return Ok((operands, None)),
+    };
+
+    let regex = compile_regex(cf, *pattern, case_insensitive)?;
+    Ok((rest, Some(regex)))
+}
+
+/// Split a trailing `if=<value>` argument off rule operands.
+///
+/// A single operand is always treated as a key, so `include if=x;` keeps its
+/// literal meaning.
+fn split_condition(
+    cf: *mut ngx_conf_t,
+    operands: &[ngx_str_t],
+) -> Result<(&[ngx_str_t], Option<CompiledComplexValue>), ()> {
+    let Some((last, rest)) = operands.split_last() else {
+        return Ok((operands, None));
+    };
+
+    let last_bytes = unsafe { std::slice::from_raw_parts(last.data, last.len) };
+    let Some(value) = last_bytes.strip_prefix(b"if=") else {
+        return Ok((operands, None));
+    };
+
+    if rest.is_empty() {
+        return Ok((operands, None));
+    }
+
+    if value.is_empty() {
+        error!(r#""if=" requires a value"#);
+        return Err(());
+    }
+
+    let value = ngx_str_t {
+        len: value.len(),
+        data: value.as_ptr().cast_mut(),
+    };
+    let condition = compile_complex_value(cf, value)?;
+    Ok((rest, Some(condition)))
+}
+
+/// `validate <key> <type> [default=<value>]`: drop a kept argument whose
+/// percent-decoded value is not a valid `<type>`, or write `<value>` instead.
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_validate_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        if args.len() != 3 && args.len() != 4 {
+            error!(r#"invalid number of arguments in "validate" directive"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let key = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        if key.is_empty() {
+            error!(r#""validate" key cannot be empty"#);
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.has_local_validation(key) {
+            error!(
+                r#""validate" is duplicate for key "{}""#,
+                String::from_utf8_lossy(key)
+            );
+            return NGX_CONF_ERROR;
+        }
+
+        let spec = unsafe { std::slice::from_raw_parts(args[2].data, args[2].len) };
+        let Ok(value_type) = ValueType::parse(&cf_ref.pool(), spec) else {
+            return NGX_CONF_ERROR;
+        };
+
+        let default = match args.get(3) {
+            None => None,
+            Some(param) => {
+                let param = unsafe { std::slice::from_raw_parts(param.data, param.len) };
+                let Some(default) = param.strip_prefix(b"default=") else {
+                    error!(r#""validate" expects "default=<value>""#);
+                    return NGX_CONF_ERROR;
+                };
+
+                if !value_type.accepts(default) {
+                    error!(
+                        r#""validate" default "{}" is not a valid {}"#,
+                        String::from_utf8_lossy(default),
+                        value_type
+                    );
+                    return NGX_CONF_ERROR;
+                }
+
+                // Written like a `set` value, so it cannot split the argument.
+                let mut escaped = std::vec::Vec::with_capacity(default.len());
+                escape_component(default, &mut escaped);
+
+                let Ok(default) = NginxStr::from_bytes(cf_ref.pool(), &escaped) else {
+                    error!("failed to allocate validate default");
+                    return NGX_CONF_ERROR;
+                };
+                Some(default)
+            }
+        };
+
+        let Ok(key) = NginxStr::from_ngx_str(cf_ref, &args[1]) else {
+            error!("failed to allocate validate key");
+            return NGX_CONF_ERROR;
+        };
+
+        filter.add_validation(
+            cf_ref.pool(),
+            Validation {
+                key,
+                value_type,
+                default,
+            },
+        );
+        NGX_CONF_OK
+    })
+}
+
+/// `max_value_length [<key>] <length> [drop | truncate]`: limit the raw length
+/// of kept values, for one key or for the whole filter.
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_max_value_length_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        // The length is always numeric, so the last word tells
+        // `<key> <length>` from `<length> <policy>`.
+        let (operands, policy) = match args[1..].split_last() {
+            Some((last, rest)) if !rest.is_empty() => {
+                match unsafe { std::slice::from_raw_parts(last.data, last.len) } {
+                    b"drop" => (rest, LengthPolicy::Drop),
+                    b"truncate" => (rest, LengthPolicy::Truncate),
+                    _ => (&args[1..], LengthPolicy::Drop),
+                }
+            }
+            _ => (&args[1..], LengthPolicy::Drop),
+        };
+
+        let (key, length) = match operands {
+            [length] => (None, length),
+            [key, length] => (Some(key), length),
+            _ => {
+                error!(r#"invalid number of arguments in "max_value_length" directive"#);
+                return NGX_CONF_ERROR;
+            }
+        };
+
+        let key_bytes = key.map(|key| unsafe { std::slice::from_raw_parts(key.data, key.len) });
+        if key_bytes.is_some_and(<[u8]>::is_empty) {
+            error!(r#""max_value_length" key cannot be empty"#);
+            return NGX_CONF_ERROR;
+        }
+
+        if filter.has_local_value_length_limit(key_bytes) {
+            match key_bytes {
+                Some(key) => error!(
+                    r#""max_value_length" is duplicate for key "{}""#,
+                    String::from_utf8_lossy(key)
+                ),
+                None => error!(r#""max_value_length" directive is duplicate"#),
+            }
+            return NGX_CONF_ERROR;
+        }
+
+        let value = unsafe { ngx_atoi(length.data, length.len) };
+        let max = match usize::try_from(value) {
+            Ok(max) if max > 0 => max,
+            _ => {
+                error!(r#""max_value_length" must be a positive integer"#);
+                return NGX_CONF_ERROR;
+            }
+        };
+
+        let key = match key {
+            Some(key) => {
+                let Ok(key) = NginxStr::from_ngx_str(cf_ref, key) else {
+                    error!("failed to allocate max_value_length key");
+                    return NGX_CONF_ERROR;
+                };
+                Some(key)
+            }
+            None => None,
+        };
+
+        filter.add_value_length_limit(cf_ref.pool(), ValueLengthLimit { key, max, policy });
+        NGX_CONF_OK
+    })
+}
+
+/// `rename <from> <to>` or `rename ~ | ~* <regex> <replacement>`: write kept
+/// arguments under a new key, with `$0` to `$9` in a regex replacement taken
+/// from its captures.
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_rename_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        if args.len() != 3 && args.len() != 4 {
+            error!(r#"invalid number of arguments in "rename" directive"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let to = args[args.len() - 1];
+        let to_bytes = unsafe { std::slice::from_raw_parts(to.data, to.len) };
+        if to_bytes.is_empty() {
+            error!(r#""rename" replacement cannot be empty"#);
+            return NGX_CONF_ERROR;
+        }
+
+        if !is_plain_key(to_bytes) {
+            error!(r#""rename" replacement must not contain spaces, "&", "=", or "#""#);
+            return NGX_CONF_ERROR;
+        }
+
+        let source = if args.len() == 3 {
+            let from = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+            if from.is_empty() {
+                error!(r#""rename" key cannot be empty"#);
+                return NGX_CONF_ERROR;
+            }
+
+            if filter.has_literal_rename(from) {
+                error!(
+                    r#""rename" is duplicate for key "{}""#,
+                    String::from_utf8_lossy(from)
+                );
+                return NGX_CONF_ERROR;
+            }
+
+            let Ok(from) = NginxStr::from_ngx_str(cf_ref, &args[1]) else {
+                error!("failed to allocate rename key");
+                return NGX_CONF_ERROR;
+            };
+            RenameSource::Literal(from)
+        } else {
+            let case_insensitive =
+                match unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) } {
+                    b"~" => false,
+                    b"~*" => true,
+                    _ => {
+                        error!(r#""rename" expects "~" or "~*" before a regex"#);
+                        return NGX_CONF_ERROR;
+                    }
+                };
+
+            let Ok(regex) = compile_regex(cf, args[2], case_insensitive) else {
+                return NGX_CONF_ERROR;
+            };
+            if check_replacement(to_bytes, regex.captures).is_err() {
+                return NGX_CONF_ERROR;
+            }
+            RenameSource::Regex(regex)
+        };
+
+        let Ok(to) = NginxStr::from_ngx_str(cf_ref, &to) else {
+            error!("failed to allocate rename replacement");
+            return NGX_CONF_ERROR;
+        };
+
+        filter.add_rename(cf_ref.pool(), Rename { source, to });
+        NGX_CONF_OK
+    })
+}
+
+/// `set <key> <value>`: replace every kept occurrence of `<key>` with one
+/// argument whose value is evaluated per request.
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_set_arg_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || add_injected_arg_directive(cf, InjectAction::Set))
+}
+
+/// `add <key> <value>`: append an argument whose value is evaluated per request.
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_add_arg_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || add_injected_arg_directive(cf, InjectAction::Add))
+}
+
+/// Shared parser for `set`/`add`: `<key> <value>`, where `<value>` may
+/// contain variables.
+fn add_injected_arg_directive(cf: *mut ngx_conf_t, action: InjectAction) -> *mut core::ffi::c_char {
+    let cf_ref = unsafe { cf.as_mut().expect("cf") };
+    let args = cf_ref.args();
+    let filter = unsafe { &mut *get_current_filter(cf) };
+    let directive = action.directive_name();
+
+    if args.len() != 3 {
+        error!(
+            r#"invalid number of arguments in "{}" directive"#,
+            directive
+        );
+        return NGX_CONF_ERROR;
+    }
+
+    let key = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+    if key.is_empty() {
+        error!(r#""{}" key cannot be empty"#, directive);
+        return NGX_CONF_ERROR;
+    }
+
+    if !is_plain_key(key) {
+        error!(
+            r#""{}" key must not contain spaces, "&", "=", or "#""#,
+            directive
+        );
+        return NGX_CONF_ERROR;
+    }
+
+    if action == InjectAction::Set && filter.has_local_set_arg(key) {
+        error!(
+            r#""set" is duplicate for key "{}""#,
+            String::from_utf8_lossy(key)
+        );
+        return NGX_CONF_ERROR;
+    }
+
+    let Ok(value) = compile_complex_value(cf, args[2]) else {
+        return NGX_CONF_ERROR;
+    };
+
+    let Ok(key) = NginxStr::from_ngx_str(cf_ref, &args[1]) else {
+        error!("failed to allocate {} key", directive);
+        return NGX_CONF_ERROR;
+    };
+
+    filter.add_injected_arg(cf_ref.pool(), InjectedArg { action, key, value });
+    NGX_CONF_OK
+}
+
+/// Return true when `key` can be written into the filtered output as is.
+fn is_plain_key(key: &[u8]) -> bool {
+    !key.iter()
+        .any(|b| *b <= b' ' || matches!(b, b'&' | b'=' | b'#'))
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_volatile_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+        let filter = unsafe { &mut *get_current_filter(cf) };
+
+        if args.len() != 1 {
+            error!(r#"invalid number of arguments in "volatile" directive"#);
+            return NGX_CONF_ERROR;
+        }
+
+        filter.volatile = true;
+        NGX_CONF_OK
+    })
+}
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_rules_file_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    _conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let args = cf_ref.args();
+
+        if args.len() != 2 {
+            error!(r#"invalid number of arguments in "rules_file" directive"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let mut path = args[1];
+        if unsafe { ngx_conf_full_name(cf_ref.cycle, &raw mut path, 1) } != NgxStatus::OK {
+            error!("failed to resolve rules file path");
+            return NGX_CONF_ERROR;
+        }
+
+        let path_bytes = unsafe { std::slice::from_raw_parts(path.data, path.len) };
+        if !path_bytes.iter().any(|b| matches!(b, b'*' | b'?' | b'[')) {
+            return unsafe { parse_rules_file(cf, path) };
+        }
+
+        let mut gl = unsafe { core::mem::zeroed::<ngx_glob_t>() };
+        gl.pattern = path.data;
+        gl.log = cf_ref.log;
+        gl.test = 1;
+
+        if unsafe { ngx_open_glob(&raw mut gl) } != NgxStatus::OK {
+            error!(r#"glob() "{}" failed"#, String::from_utf8_lossy(path_bytes));
+            return NGX_CONF_ERROR;
+        }
+
+        let mut rv = NGX_CONF_OK;
+        let mut name = ngx_str_t::default();
+
+        while unsafe { ngx_read_glob(&raw mut gl, &raw mut name) } == NgxStatus::OK {
+            // Glob results are released by `ngx_close_glob`; keep a NUL-terminated copy.
+            name.len += 1;
+            let data = unsafe { ngx_pstrdup(cf_ref.pool, &raw mut name) };
+            if data.is_null() {
+                error!("failed to allocate rules file name");
+                rv = NGX_CONF_ERROR;
+                break;
+            }
+
+            let file = ngx_str_t {
+                len: name.len - 1,
+                data,
+            };
+
+            rv = unsafe { parse_rules_file(cf, file) };
+            if rv != NGX_CONF_OK {
+                break;
+            }
+        }
+
+        unsafe { ngx_close_glob(&raw mut gl) };
+        rv
+    })
+}
+
+/// Load `file` one rule per line. Each line is split into words and passed to
+/// the rule handlers as a directive of the block, with errors reported against
+/// the rules file and line.
+unsafe fn parse_rules_file(cf: *mut ngx_conf_t, file: ngx_str_t) -> *mut core::ffi::c_char {
+    let cf_ref = unsafe { cf.as_mut().expect("cf") };
+    let name = unsafe { std::slice::from_raw_parts(file.data, file.len) };
+
+    let mut handle = match std::fs::File::open(OsStr::from_bytes(name)) {
+        Ok(handle) => handle,
+        Err(err) => {
+            error!(
+                r#"open() "{}" failed ({err})"#,
+                String::from_utf8_lossy(name)
+            );
+            return NGX_CONF_ERROR;
+        }
+    };
+
+    let mut contents = std::vec::Vec::new();
+    if let Err(err) = handle.read_to_end(&mut contents) {
+        error!(
+            r#"read() "{}" failed ({err})"#,
+            String::from_utf8_lossy(name)
+        );
+        return NGX_CONF_ERROR;
+    }
+
+    let mut conf_file = unsafe { core::mem::zeroed::<ngx_conf_file_t>() };
+    conf_file.file.fd = handle.as_raw_fd();
+    conf_file.file.name = file;
+    conf_file.file.log = cf_ref.log;
+
+    let mut file_cf = *cf_ref;
+    file_cf.conf_file = &raw mut conf_file;
+    let file_cf: *mut ngx_conf_t = &raw mut file_cf;
+
+    for (line, text) in (1..).zip(contents.split(|&b| b == b'\n')) {
+        unsafe { (*(*file_cf).conf_file).line = line };
+
+        let rv = with_config_context(file_cf, || {
+            let words = match split_rule_line(text) {
+                Ok(words) if words.is_empty() => return NGX_CONF_OK,
+                Ok(words) => words,
+                Err(msg) => {
+                    error!("{msg}");
+                    return NGX_CONF_ERROR;
+                }
+            };
+
+            let handler = match words[0].as_slice() {
+                b"include" => args_filter_include_set,
+                b"exclude" => args_filter_exclude_set,
+                b"exclude_value" => args_filter_exclude_value_set,
+                _ => {
+                    error!(
+                        r#"only "include", "exclude", and "exclude_value" are allowed in rules files"#
+                    );
+                    return NGX_CONF_ERROR;
+                }
+            };
+
+            if unsafe { set_rule_args(file_cf, &words) }.is_err() {
+                error!("failed to allocate rules file arguments");
+                return NGX_CONF_ERROR;
+            }
+
+            handler(file_cf, core::ptr::null_mut(), unsafe {
+                (*file_cf).handler_conf
+            })
+        });
+
+        if rv != NGX_CONF_OK {
+            return rv;
+        }
+    }
+
+    NGX_CONF_OK
+}
+
+/// Replace the arguments of `cf` with `words`, copied into its pool with a
+/// terminating NUL as the nginx tokenizer leaves them.
+unsafe fn set_rule_args(cf: *mut ngx_conf_t, words: &[std::vec::Vec<u8>]) -> Result<(), ()> {
+    let cf_ref = unsafe { cf.as_mut().expect("cf") };
+    let args = unsafe { cf_ref.args.as_mut().ok_or(())? };
+    args.nelts = 0;
+
+    for word in words {
+        let data = cf_ref.pool().alloc_unaligned(word.len() + 1).cast::<u8>();
+        if data.is_null() {
+            return Err(());
+        }
+
+        let Some(arg) = (unsafe { ngx_array_push(args).cast::<ngx_str_t>().as_mut() }) else {
+            return Err(());
+        };
+
+        unsafe {
+            core::ptr::copy_nonoverlapping(word.as_ptr(), data, word.len());
+            *data.add(word.len()) = 0;
+        }
+        *arg = ngx_str_t {
+            len: word.len(),
+            data,
+        };
+    }
+
+    Ok(())
+}
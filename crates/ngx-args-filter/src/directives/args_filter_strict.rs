@@ -0,0 +1,59 @@
+//! `args_filter_strict` directive implementation.
+//!
+//! Turns lint findings for `args_filter` blocks from warnings into errors.
+
+#![allow(static_mut_refs)]
+
+use crate::conf_ext::NgxConfExt;
+use crate::config::MainConf;
+use crate::logging::with_config_context;
+use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK};
+use ngx::ffi::{NGX_CONF_FLAG, NGX_HTTP_MAIN_CONF, ngx_command_t, ngx_conf_t};
+use tracing::error;
+
+#[unsafe(no_mangle)]
+pub static mut ARGS_FILTER_STRICT_COMMAND: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("args_filter_strict"),
+    type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_FLAG) as _,
+    set: Some(args_filter_strict_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_strict_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let main_conf = unsafe { conf.cast::<MainConf>().as_mut().expect("main_conf") };
+        let args = cf_ref.args();
+
+        if main_conf.strict_set {
+            error!(r#""args_filter_strict" directive is duplicate"#);
+            return NGX_CONF_ERROR;
+        }
+
+        // Filters are linted as their block closes, so the mode must be known first.
+        if main_conf.variables.is_some() {
+            error!(r#""args_filter_strict" must precede all "args_filter" blocks"#);
+            return NGX_CONF_ERROR;
+        }
+
+        let value = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        main_conf.strict = if value.eq_ignore_ascii_case(b"on") {
+            true
+        } else if value.eq_ignore_ascii_case(b"off") {
+            false
+        } else {
+            error!(r#""args_filter_strict" must be "on" or "off""#);
+            return NGX_CONF_ERROR;
+        };
+
+        main_conf.strict_set = true;
+        NGX_CONF_OK
+    })
+}
@@ -0,0 +1,156 @@
+//! `args_filter_zone` directive implementation.
+//!
+//! Declares a shared memory zone holding a runtime-updatable key set that
+//! `include @zone:name;` and `exclude @zone:name;` consult on every request.
+
+#![allow(static_mut_refs)]
+
+use crate::NgxArgsFilterModule;
+use crate::conf_ext::NgxConfExt;
+use crate::config::MainConf;
+use crate::config::zone::{KeyZone, args_filter_zone_init};
+use crate::logging::with_config_context;
+use crate::nginx_str::NginxStr;
+use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK};
+use ngx::ffi::{
+    NGX_CONF_TAKE2, NGX_HTTP_MAIN_CONF, ngx_command_t, ngx_conf_t, ngx_pagesize, ngx_parse_size,
+    ngx_shared_memory_add,
+};
+use ngx::http::HttpModuleMainConf;
+use tracing::error;
+
+#[unsafe(no_mangle)]
+pub static mut ARGS_FILTER_ZONE_COMMAND: ngx_command_t = ngx_command_t {
+    name: ngx::ngx_string!("args_filter_zone"),
+    type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE2) as _,
+    set: Some(args_filter_zone_set),
+    conf: 0,
+    offset: 0,
+    post: core::ptr::null_mut(),
+};
+
+#[unsafe(no_mangle)]
+extern "C" fn args_filter_zone_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    conf: *mut core::ffi::c_void,
+) -> *mut core::ffi::c_char {
+    with_config_context(cf, || {
+        let cf_ref = unsafe { cf.as_mut().expect("cf") };
+        let main_conf = unsafe { conf.cast::<MainConf>().as_mut().expect("main_conf") };
+        let args = cf_ref.args();
+
+        let raw_name = unsafe { std::slice::from_raw_parts(args[1].data, args[1].len) };
+        if validate_zone_name(raw_name).is_err() {
+            return NGX_CONF_ERROR;
+        }
+
+        let Ok(zone_name) = NginxStr::from_bytes(cf_ref.pool(), raw_name) else {
+            error!("failed to allocate args_filter_zone name");
+            return NGX_CONF_ERROR;
+        };
+
+        let mut size_arg = args[2];
+        let size = unsafe { ngx_parse_size(&raw mut size_arg) };
+        let Ok(size) = usize::try_from(size) else {
+            error!(
+                r#"invalid size "{}" in "args_filter_zone" directive"#,
+                String::from_utf8_lossy(unsafe {
+                    std::slice::from_raw_parts(size_arg.data, size_arg.len)
+                })
+            );
+            return NGX_CONF_ERROR;
+        };
+
+        if size < 8 * unsafe { ngx_pagesize } {
+            error!(r#"args_filter_zone "{}" is too small"#, zone_name);
+            return NGX_CONF_ERROR;
+        }
+
+        if main_conf.zones.is_none() {
+            let Ok(map) = ngx::collections::RbTreeMap::try_new_in(cf_ref.pool()) else {
+                error!("failed to initialize args_filter_zone map");
+                return NGX_CONF_ERROR;
+            };
+            main_conf.zones = Some(map);
+        }
+
+        if main_conf
+            .zones
+            .as_ref()
+            .is_some_and(|m| m.get(zone_name.as_bytes()).is_some())
+        {
+            error!("duplicate args_filter_zone declaration for {}", zone_name);
+            return NGX_CONF_ERROR;
+        }
+
+        let mut shm_name = zone_name.as_ngx_str();
+        let shm_zone = unsafe {
+            ngx_shared_memory_add(
+                cf,
+                &raw mut shm_name,
+                size,
+                (&raw const crate::ngx_http_ngx_args_filter_module)
+                    .cast_mut()
+                    .cast(),
+            )
+        };
+        if shm_zone.is_null() {
+            return NGX_CONF_ERROR;
+        }
+
+        unsafe { (*shm_zone).init = Some(args_filter_zone_init) };
+
+        let Some(zones) = main_conf.zones.as_mut() else {
+            error!("args_filter_zone map unavailable");
+            return NGX_CONF_ERROR;
+        };
+
+        if zones
+            .try_insert(zone_name, KeyZone { zone: shm_zone })
+            .is_err()
+        {
+            error!("failed to store args_filter_zone");
+            return NGX_CONF_ERROR;
+        }
+
+        NGX_CONF_OK
+    })
+}
+
+/// Resolve an `@zone:name` reference to a declared `args_filter_zone`.
+pub fn find_zone(cf: &ngx_conf_t, reference: &[u8]) -> Result<KeyZone, ()> {
+    let Some(name) = reference.strip_prefix(b"@zone:") else {
+        error!("args_filter_zone reference must start with '@zone:'");
+        return Err(());
+    };
+
+    validate_zone_name(name)?;
+
+    NgxArgsFilterModule::main_conf(cf)
+        .and_then(|c| c.zones.as_ref())
+        .and_then(|m| m.get(name))
+        .copied()
+        .ok_or_else(|| {
+            error!("unknown args_filter_zone {}", String::from_utf8_lossy(name));
+        })
+}
+
+fn validate_zone_name(name: &[u8]) -> Result<(), ()> {
+    if name.is_empty() {
+        error!("args_filter_zone name cannot be empty");
+        return Err(());
+    }
+
+    if !name.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_') {
+        error!("args_filter_zone name contains invalid characters");
+        return Err(());
+    }
+
+    Ok(())
+}
+
+/// Return true when `reference` names a shared zone rather than a key list.
+pub fn is_zone_reference(reference: &[u8]) -> bool {
+    reference.starts_with(b"@zone:")
+}
@@ -3,7 +3,12 @@
 #![allow(static_mut_refs)]
 
 pub mod args_filter;
+pub mod args_filter_api;
+pub mod args_filter_dump;
+pub mod args_filter_keys;
 pub mod args_filter_nested;
+pub mod args_filter_strict;
+pub mod args_filter_zone;
 
 use ngx::ffi::ngx_command_t;
 
@@ -21,7 +26,12 @@ pub const NGX_EMPTY_COMMAND: ngx_command_t = ngx_command_t {
 };
 
 #[unsafe(no_mangle)]
-pub static mut DIRECTIVES: [ngx_command_t; 2] = [
+pub static mut DIRECTIVES: [ngx_command_t; 7] = [
     unsafe { args_filter::ARGS_FILTER_COMMAND },
+    unsafe { args_filter_keys::ARGS_FILTER_KEYS_COMMAND },
+    unsafe { args_filter_zone::ARGS_FILTER_ZONE_COMMAND },
+    unsafe { args_filter_api::ARGS_FILTER_API_COMMAND },
+    unsafe { args_filter_dump::ARGS_FILTER_DUMP_COMMAND },
+    unsafe { args_filter_strict::ARGS_FILTER_STRICT_COMMAND },
     NGX_EMPTY_COMMAND,
 ];
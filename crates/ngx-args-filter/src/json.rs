@@ -0,0 +1,30 @@
+//! Minimal JSON writer for admin endpoint output.
+
+use std::fmt::Write;
+
+/// Append `value` as a JSON string literal; invalid UTF-8 is replaced lossily.
+pub fn push_str(out: &mut String, value: &[u8]) {
+    out.push('"');
+    for ch in String::from_utf8_lossy(value).chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Append `value` as a JSON string literal, or `null` when absent.
+pub fn push_opt_str(out: &mut String, value: Option<&[u8]>) {
+    match value {
+        Some(value) => push_str(out, value),
+        None => out.push_str("null"),
+    }
+}
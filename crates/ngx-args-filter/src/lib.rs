@@ -9,15 +9,17 @@
 mod conf_ext;
 mod config;
 mod directives;
+mod json;
 mod logging;
 mod nginx_str;
+mod response;
 mod status;
 mod version;
 
-use config::MainConf;
+use config::{LocConf, MainConf, SrvConf};
 use ngx::{
     ffi::{NGX_HTTP_MODULE, ngx_module_t},
-    http::{HttpModule, HttpModuleMainConf},
+    http::{HttpModule, HttpModuleLocationConf, HttpModuleMainConf, HttpModuleServerConf},
 };
 
 pub struct NgxArgsFilterModule;
@@ -26,6 +28,14 @@ unsafe impl HttpModuleMainConf for NgxArgsFilterModule {
     type MainConf = MainConf;
 }
 
+unsafe impl HttpModuleServerConf for NgxArgsFilterModule {
+    type ServerConf = SrvConf;
+}
+
+unsafe impl HttpModuleLocationConf for NgxArgsFilterModule {
+    type LocationConf = LocConf;
+}
+
 impl HttpModule for NgxArgsFilterModule {
     fn module() -> &'static ngx_module_t {
         unsafe { &*core::ptr::addr_of!(ngx_http_ngx_args_filter_module) }
@@ -43,6 +53,16 @@ impl NgxArgsFilterModule {
         use crate::config::init::init_main_config;
         unsafe { init_main_config(cf, conf) }
     }
+
+    /// # Safety
+    ///
+    /// Caller must pass valid pointers from NGINX config phase.
+    pub unsafe extern "C" fn create_loc_conf(
+        cf: *mut ngx::ffi::ngx_conf_t,
+    ) -> *mut core::ffi::c_void {
+        use crate::config::loc::create_loc_config;
+        unsafe { create_loc_config(cf) }
+    }
 }
 
 ngx::ngx_modules!(ngx_http_ngx_args_filter_module);
@@ -64,8 +84,8 @@ pub static ngx_http_ngx_args_filter_module_ctx: ngx::ffi::ngx_http_module_t =
         postconfiguration: None,
         create_main_conf: Some(NgxArgsFilterModule::create_main_conf),
         init_main_conf: Some(NgxArgsFilterModule::init_main_conf),
-        create_srv_conf: None,
-        merge_srv_conf: None,
-        create_loc_conf: None,
-        merge_loc_conf: None,
+        create_srv_conf: Some(NgxArgsFilterModule::create_srv_conf),
+        merge_srv_conf: Some(NgxArgsFilterModule::merge_srv_conf),
+        create_loc_conf: Some(NgxArgsFilterModule::create_loc_conf),
+        merge_loc_conf: Some(NgxArgsFilterModule::merge_loc_conf),
     };
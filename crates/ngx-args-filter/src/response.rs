@@ -0,0 +1,55 @@
+//! Helpers for content handlers that answer with a small in-memory body.
+
+use crate::status::NgxStatus;
+use ngx::ffi::{
+    NGX_HTTP_OK, ngx_chain_t, ngx_create_temp_buf, ngx_http_output_filter, ngx_http_request_t,
+    ngx_http_send_header, ngx_int_t, ngx_str_t, ngx_uint_t,
+};
+
+/// Send a `200` response with `body` as its content.
+///
+/// # Safety
+///
+/// Caller must pass a valid request from a content handler.
+pub unsafe fn send_body(
+    r: *mut ngx_http_request_t,
+    content_type: ngx_str_t,
+    body: &[u8],
+) -> ngx_int_t {
+    let Ok(content_length) = i64::try_from(body.len()) else {
+        return NgxStatus::ERROR;
+    };
+
+    unsafe {
+        (*r).headers_out.status = NGX_HTTP_OK as ngx_uint_t;
+        (*r).headers_out.content_length_n = content_length;
+        (*r).headers_out.content_type = content_type;
+        (*r).headers_out.content_type_len = content_type.len;
+        if body.is_empty() {
+            (*r).set_header_only(1);
+        }
+    }
+
+    let rc = unsafe { ngx_http_send_header(r) };
+    if rc == NgxStatus::ERROR || rc > NgxStatus::OK || unsafe { (*r).header_only() } != 0 {
+        return rc;
+    }
+
+    let buf = unsafe { ngx_create_temp_buf((*r).pool, body.len()) };
+    if buf.is_null() {
+        return NgxStatus::ERROR;
+    }
+
+    unsafe {
+        core::ptr::copy_nonoverlapping(body.as_ptr(), (*buf).last, body.len());
+        (*buf).last = (*buf).last.add(body.len());
+        (*buf).set_last_buf(1);
+        (*buf).set_last_in_chain(1);
+    }
+
+    let mut out = ngx_chain_t {
+        buf,
+        next: core::ptr::null_mut(),
+    };
+    unsafe { ngx_http_output_filter(r, &raw mut out) }
+}
@@ -1,6 +1,9 @@
 //! Typed wrappers for common NGINX status codes.
 
-use ngx::ffi::{NGX_DECLINED, NGX_ERROR, NGX_OK, ngx_int_t};
+use ngx::ffi::{
+    NGX_DECLINED, NGX_ERROR, NGX_HTTP_BAD_REQUEST, NGX_HTTP_INSUFFICIENT_STORAGE,
+    NGX_HTTP_NO_CONTENT, NGX_HTTP_NOT_ALLOWED, NGX_HTTP_NOT_FOUND, NGX_OK, ngx_int_t,
+};
 
 #[allow(clippy::cast_possible_wrap)]
 impl NgxStatus {
@@ -10,3 +13,14 @@ impl NgxStatus {
 }
 
 pub struct NgxStatus;
+
+#[allow(clippy::cast_possible_wrap)]
+impl NgxHttpStatus {
+    pub const NO_CONTENT: ngx_int_t = NGX_HTTP_NO_CONTENT as ngx_int_t;
+    pub const BAD_REQUEST: ngx_int_t = NGX_HTTP_BAD_REQUEST as ngx_int_t;
+    pub const NOT_FOUND: ngx_int_t = NGX_HTTP_NOT_FOUND as ngx_int_t;
+    pub const NOT_ALLOWED: ngx_int_t = NGX_HTTP_NOT_ALLOWED as ngx_int_t;
+    pub const INSUFFICIENT_STORAGE: ngx_int_t = NGX_HTTP_INSUFFICIENT_STORAGE as ngx_int_t;
+}
+
+pub struct NgxHttpStatus;